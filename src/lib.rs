@@ -0,0 +1,15 @@
+pub mod nes;
+
+pub use nes::Frame;
+pub use nes::Nes;
+pub use nes::controller::Button;
+pub use nes::nestest::NestestResult;
+pub use nes::rewind::Rewind;
+pub use nes::ricoh2c02::palette::Palette;
+pub use nes::rom::Rom;
+pub use nes::rom::RomError;
+pub use nes::state::StateError;
+pub use nes::test_rom::TestOutcome;
+pub use nes::test_rom::TestResult;
+pub use nes::tracer::TraceCondition;
+pub use nes::tracer::Tracer;
@@ -1,49 +1,412 @@
+extern crate rnes;
 extern crate sdl2;
 
-mod nes;
+mod options;
+mod pacing;
 mod util;
 
-use nes::bus::Bus;
-use nes::mapper::create_mapper;
-use nes::ricoh2c02::Ricoh2C02;
-use nes::ricoh2a03::InterruptType;
-use nes::ricoh2a03::Ricoh2A03;
-use nes::rom::Rom;
+use rnes::Button;
+use rnes::Nes;
+use rnes::Palette;
+use rnes::Rewind;
+use rnes::nes::nestest;
+use rnes::nes::ricoh2a03::apu::mixer::Channel;
+use rnes::nes::ricoh2c02;
+use rnes::nes::ricoh2c02::ntsc::NtscFilter;
+use rnes::nes::ricoh2c02::ntsc::NTSC_WIDTH;
+use rnes::nes::ricoh2c02::palette::NtscSettings;
+use rnes::nes::SCREEN_HEIGHT;
+use rnes::nes::SCREEN_WIDTH;
+use rnes::nes::test_rom;
+use rnes::Rom;
+use rnes::RomError;
+use rnes::TestOutcome;
+use rnes::Tracer;
+use sdl2::audio::AudioQueue;
+use sdl2::audio::AudioSpecDesired;
 use sdl2::event::*;
 use sdl2::keyboard::*;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
+use options::Options;
+use options::PaletteSource;
+use options::TestOptions;
+use options::TraceStart;
+use pacing::Pacer;
+use pacing::PacingMode;
 use std::env;
-use std::thread;
-use std::time;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::time::Instant;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
 use util::open_file;
+use util::read_file;
+use util::save_path;
+use util::write_file;
 
-pub const FRAME_TIME: f64 = (1.0 / 60.0) * 1000.0;
+pub const BATTERY_SAVE_INTERVAL: u64 = 600;
+
+pub const AUDIO_SAMPLE_RATE: i32 = 44100;
+
+fn keycode_to_button(keycode: Keycode) -> Option<Button> {
+	match keycode {
+		Keycode::A		=> Some(Button::A),
+		Keycode::S		=> Some(Button::B),
+		Keycode::Z		=> Some(Button::Select),
+		Keycode::X		=> Some(Button::Start),
+		Keycode::Up		=> Some(Button::Up),
+		Keycode::Down	=> Some(Button::Down),
+		Keycode::Left	=> Some(Button::Left),
+		Keycode::Right	=> Some(Button::Right),
+		_ => None,
+	}
+}
+
+// In sample frames, so stereo counts the same as mono.
+fn queued_samples(queue: &AudioQueue<f32>) -> u32 {
+	queue.size() / (mem::size_of::<f32>() as u32 * queue.spec().channels as u32)
+}
+
+// Ctrl with 1 to 6 toggles the channels in mixer order.
+fn keycode_to_channel(keycode: Keycode) -> Option<Channel> {
+	let index = keycode_to_slot(keycode)?.checked_sub(1)?;
+	Channel::ALL.get(index).copied()
+}
+
+fn toggle_channel(nes: &mut Nes, channel: Channel) {
+	let mut settings = nes.channel_settings(channel);
+	settings.enabled = !settings.enabled;
+	nes.set_channel_settings(channel, settings);
+
+	println!("{} {}", channel.name(), if settings.enabled { "on" } else { "off" });
+}
+
+fn load_nes(rom_filepath: &Path) -> Result<Nes, RomError> {
+	let mut rom_file = open_file(rom_filepath)?;
+	let rom = Rom::new(&mut rom_file)?;
+
+	println!("Mapper #{}", rom.mapper());
+
+	Nes::new(rom)
+}
+
+fn save_battery_ram(nes: &Nes, path: &Path, last_saved: &mut Option<Vec<u8>>) {
+	let ram = match nes.battery_ram() {
+		Some(ram) => ram,
+		None => return,
+	};
+
+	if last_saved.as_ref() == Some(&ram) {
+		return;
+	}
+
+	match write_file(path, &ram) {
+		Ok(()) => *last_saved = Some(ram),
+		Err(e) => eprintln!("rnes: failed to write {}: {}", path.display(), e),
+	}
+}
+
+fn create_tracer(options: &Options) -> Option<Tracer> {
+	let path = options.trace_path.as_ref()?;
+
+	let mut tracer = match Tracer::create(path) {
+		Ok(tracer) => tracer,
+		Err(e) => {
+			eprintln!("rnes: failed to create {}: {}", path.display(), e);
+			return None;
+		}
+	};
+
+	match options.trace_start {
+		Some(TraceStart::Condition(condition)) => tracer.start_at(condition),
+		Some(TraceStart::Key) => tracer.set_enabled(false),
+		None => {},
+	}
+
+	if let Some(condition) = options.trace_stop {
+		tracer.stop_at(condition);
+	}
+
+	Some(tracer)
+}
+
+fn load_palette(source: &PaletteSource) -> Result<Palette, String> {
+	match *source {
+		PaletteSource::Default => Ok(Palette::default()),
+		PaletteSource::Ntsc(ref settings) => Ok(Palette::ntsc(settings)),
+		PaletteSource::File(ref path) => {
+			let mut file = open_file(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+			Palette::from_pal(&mut file).map_err(|e| format!("failed to load {}: {}", path.display(), e))
+		},
+	}
+}
+
+fn keycode_to_slot(keycode: Keycode) -> Option<usize> {
+	match keycode {
+		Keycode::Num0 => Some(0),
+		Keycode::Num1 => Some(1),
+		Keycode::Num2 => Some(2),
+		Keycode::Num3 => Some(3),
+		Keycode::Num4 => Some(4),
+		Keycode::Num5 => Some(5),
+		Keycode::Num6 => Some(6),
+		Keycode::Num7 => Some(7),
+		Keycode::Num8 => Some(8),
+		Keycode::Num9 => Some(9),
+		_ => None,
+	}
+}
+
+fn state_path(options: &Options, slot: usize) -> PathBuf {
+	save_path(&options.rom_path, options.save_dir.as_deref(), &format!("ss{}", slot))
+}
+
+fn save_state(nes: &Nes, options: &Options, slot: usize) {
+	let path = state_path(options, slot);
+
+	match write_file(&path, &nes.save_state()) {
+		Ok(()) => println!("Saved state to slot {}", slot),
+		Err(e) => eprintln!("rnes: failed to write {}: {}", path.display(), e),
+	}
+}
+
+fn load_state(nes: &mut Nes, options: &Options, slot: usize) {
+	let path = state_path(options, slot);
+
+	let data = match read_file(&path) {
+		Ok(data) => data,
+		Err(e) => {
+			eprintln!("rnes: failed to read {}: {}", path.display(), e);
+			return;
+		}
+	};
+
+	match nes.load_state(&data) {
+		Ok(()) => println!("Loaded state from slot {}", slot),
+		Err(e) => eprintln!("rnes: failed to load {}: {}", path.display(), e),
+	}
+}
+
+// `--nestest`: runs nestest.nes from $C000 without a window, checking its
+// trace against a reference log if one was given. Exits with 0 if it passed.
+fn run_nestest(nes: &mut Nes, options: &Options) -> i32 {
+	let reference = match options.nestest_log.as_ref().map(read_file) {
+		Some(Ok(data)) => Some(String::from_utf8_lossy(&data).into_owned()),
+		Some(Err(e)) => {
+			eprintln!("rnes: failed to read {}: {}", options.nestest_log.as_ref().unwrap().display(), e);
+			return 2;
+		},
+		None => None,
+	};
+
+	let result = nestest::run_nestest(nes, reference.as_deref());
+	finish_trace(nes);
+
+	if let Some(divergence) = result.divergence.as_ref() {
+		println!("Trace diverges from the reference at line {}:", divergence.line);
+
+		let first = divergence.line - divergence.context.len();
+		for (i, line) in divergence.context.iter().enumerate() {
+			println!("  {:>5}  {}", first + i, line);
+		}
+
+		println!("expected  {}", divergence.expected.as_deref().unwrap_or("(end of log)"));
+		println!("  actual  {}", divergence.actual.as_deref().unwrap_or("(end of run)"));
+	}
+
+	if !result.finished {
+		println!("Stopped before reaching ${:04X}", nestest::NESTEST_END);
+	}
+
+	println!("{} instructions, official result ${:02X}, unofficial result ${:02X}",
+		result.instructions, result.official, result.unofficial);
+
+	if result.passed() { 0 } else { 1 }
+}
+
+fn finish_trace(nes: &mut Nes) {
+	if let Some(tracer) = nes.tracer() {
+		tracer.flush();
+
+		if let Some(e) = tracer.error() {
+			eprintln!("rnes: failed to write trace: {}", e);
+		}
+	}
+}
+
+// `rnes test`: runs a blargg-style test ROM headless and exits with 0 if it
+// passed, 1 if it failed and 2 if it couldn't be run to a result.
+fn run_test<I: Iterator<Item = String>>(args: I) -> i32 {
+	let options = match TestOptions::parse(args) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("rnes: {}", e);
+			eprintln!("{}", options::usage());
+			return 2;
+		}
+	};
+
+	let mut nes = match load_nes(&options.rom_path) {
+		Ok(nes) => nes,
+		Err(e) => {
+			eprintln!("rnes: failed to load {}: {}", options.rom_path.display(), e);
+			return 2;
+		}
+	};
+
+	nes.power_on();
+	let result = test_rom::run_test_rom(&mut nes, options.frame_limit);
+
+	if !result.text.is_empty() {
+		println!("{}", result.text.trim_end());
+	}
+
+	match result.outcome {
+		TestOutcome::Passed => {
+			println!("Passed after {} frames", result.frames);
+			0
+		},
+		TestOutcome::Failed(code) => {
+			println!("Failed with code {} after {} frames", code, result.frames);
+			1
+		},
+		TestOutcome::Timeout => {
+			println!("Timed out after {} frames", result.frames);
+			2
+		},
+		TestOutcome::NoSignature => {
+			println!("No test status at $6000 after {} frames", result.frames);
+			2
+		},
+		TestOutcome::Halted(opcode) => {
+			println!("CPU halted by JAM opcode 0x{:02x} after {} frames", opcode, result.frames);
+			2
+		},
+	}
+}
 
 fn main() {
-	let rom_filepath = env::args().nth(1).unwrap();
-	let mut rom_file = open_file(rom_filepath).unwrap();
-	let rom = Rom::new(&mut rom_file);
+	let mut args = env::args().skip(1).peekable();
+
+	if args.peek().map(String::as_str) == Some("test") {
+		args.next();
+		process::exit(run_test(args));
+	}
+
+	let options = match Options::parse(args) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("rnes: {}", e);
+			eprintln!("{}", options::usage());
+			process::exit(1);
+		}
+	};
+
+	let mut nes = match load_nes(&options.rom_path) {
+		Ok(nes) => nes,
+		Err(e) => {
+			eprintln!("rnes: failed to load {}: {}", options.rom_path.display(), e);
+			process::exit(1);
+		}
+	};
+
+	let palette = match load_palette(&options.palette) {
+		Ok(palette) => palette,
+		Err(e) => {
+			eprintln!("rnes: {}", e);
+			process::exit(1);
+		}
+	};
+
+	let battery_path = save_path(&options.rom_path, options.save_dir.as_deref(), "sav");
+	let mut battery_saved = None;
+
+	if nes.battery_ram().is_some() {
+		if let Ok(data) = read_file(&battery_path) {
+			nes.load_battery_ram(&data);
+			battery_saved = Some(data);
+		}
+	}
 
-	let mapper = Rc::new(RefCell::new(create_mapper(rom)));
+	nes.power_on();
+	nes.set_tracer(create_tracer(&options));
+
+	if options.nestest {
+		process::exit(run_nestest(&mut nes, &options));
+	}
 
-	let ppu = Ricoh2C02::new(mapper.clone());
-	let bus = Bus::new(mapper, ppu);
-	let mut cpu = Ricoh2A03::new(bus);
-	cpu.reset();
+	// The filter's output is twice as wide, so the window doubles in size to
+	// keep the aspect ratio.
+	let mut ntsc_filter = options.ntsc_filter.map(|preset| {
+		let settings = match options.palette {
+			PaletteSource::Ntsc(settings) => settings,
+			_ => NtscSettings::default(),
+		};
+
+		NtscFilter::new(preset, &settings, &palette)
+	});
+
+	let (texture_width, scale) = match ntsc_filter {
+		Some(_) => (NTSC_WIDTH as u32, 2),
+		None => (SCREEN_WIDTH as u32, 1),
+	};
 
 	let sdl_context = sdl2::init().unwrap();
 	let mut sdl_event = sdl_context.event_pump().unwrap();
 	let sdl_video = sdl_context.video().unwrap();
 
-	let sdl_window = sdl_video.window("rnes", 256, 224).build().unwrap();
-	let mut sdl_canvas = sdl_window.into_canvas().build().unwrap();
+	for (&channel, &settings) in Channel::ALL.iter().zip(options.channels.iter()) {
+		nes.set_channel_settings(channel, settings);
+	}
+
+	let audio_spec = AudioSpecDesired {
+		freq: Some(AUDIO_SAMPLE_RATE),
+		channels: Some(if nes.stereo_audio() { 2 } else { 1 }),
+		samples: Some(1024),
+	};
+
+	let audio_queue = match sdl_context.audio().and_then(|audio| audio.open_queue::<f32, _>(None, &audio_spec)) {
+		Ok(queue) => Some(queue),
+		Err(e) => {
+			eprintln!("rnes: no audio: {}", e);
+			None
+		}
+	};
+
+	let sample_rate = audio_queue.as_ref().map_or(AUDIO_SAMPLE_RATE, |queue| queue.spec().freq);
+	let mut pacing = options.pacing;
+
+	if pacing == PacingMode::Audio && audio_queue.is_none() {
+		pacing = PacingMode::Sleep;
+	}
+
+	// Vsync only keeps time on a display refreshing close to the NES.
+	if pacing == PacingMode::Vsync {
+		match sdl_video.current_display_mode(0) {
+			Ok(mode) if (mode.refresh_rate - 60).abs() <= 1 => {},
+			_ => {
+				eprintln!("rnes: display is not running at 60 Hz, pacing frames by sleeping instead");
+				pacing = PacingMode::Sleep;
+			}
+		}
+	}
+
+	let mut pacer = Pacer::new(pacing, sample_rate as u32);
+
+	if let Some(queue) = audio_queue.as_ref() {
+		nes.set_audio_profile(options.audio_profile);
+		nes.set_sample_rate(sample_rate as f64);
+		queue.resume();
+	}
+
+	let sdl_window = sdl_video.window("rnes", 256 * scale, 224 * scale).build().unwrap();
+	let mut sdl_canvas = match pacer.mode() {
+		PacingMode::Vsync => sdl_window.into_canvas().present_vsync().build().unwrap(),
+		_ => sdl_window.into_canvas().build().unwrap(),
+	};
 	let sdl_texture_creator = sdl_canvas.texture_creator();
 	let mut sdl_texture = sdl_texture_creator.create_texture_streaming(
-							PixelFormatEnum::RGB24, 256, 240).unwrap();
+							PixelFormatEnum::RGB24, texture_width, SCREEN_HEIGHT as u32).unwrap();
 
 	//let nt_window = sdl_video.window("rnes nametables", 512, 480).build().unwrap();
 	//let mut nt_canvas = nt_window.into_canvas().build().unwrap();
@@ -58,71 +421,136 @@ fn main() {
 	//						PixelFormatEnum::RGB24, 512, 480).unwrap();
 
 	let mut running = true;
-
-	let mut start_time = Instant::now();
-	let mut end_time;
-	let mut elapsed;
-	let mut elapsed_ms;
-	let mut sleep_time;
+	let mut buttons = 0;
+	let mut state_slot = 0;
+	let mut rewinding = false;
+	let mut halted = false;
+	let mut rewind = Rewind::new(options.rewind_interval, options.rewind_budget);
 
 	while running {
-		if cpu.should_nmi() {
-			cpu.interrupt(InterruptType::NMI);
-		}
+		let mut audio_queued = false;
 
-		if cpu.should_irq() {
-			cpu.interrupt(InterruptType::IRQ);
-		}
+		for event in sdl_event.poll_iter() {
+			match event {
+				Event::Quit {..} => {
+					running = false;
+				},
 
-		cpu.step();
+				Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
+					running = false;
+				},
 
-		if cpu.should_redraw() {
-			for event in sdl_event.poll_iter() {
-				match event {
-					Event::Quit {..} => {
-						running = false;
-					},
+				Event::KeyDown {keycode: Some(Keycode::F5), repeat: false, ..} => {
+					save_state(&nes, &options, state_slot);
+				},
 
-					Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
-						running = false;
-					},
+				Event::KeyDown {keycode: Some(Keycode::F7), repeat: false, ..} => {
+					load_state(&mut nes, &options, state_slot);
+					rewind.clear();
+				},
 
-					Event::KeyDown {keycode, ..} => {
-						cpu.set_button(keycode.unwrap(), true);
-					},
+				Event::KeyDown {keycode: Some(Keycode::F9), repeat: false, ..} => {
+					if let Some(tracer) = nes.tracer() {
+						tracer.toggle();
+						println!("Tracing {}", if tracer.enabled() { "on" } else { "off" });
+					}
+				},
 
-					Event::KeyUp {keycode, ..} => {
-						cpu.set_button(keycode.unwrap(), false);
-					},
+				Event::KeyDown {keycode: Some(keycode), keymod, repeat, ..} if keymod.intersects(LCTRLMOD | RCTRLMOD) && keycode_to_channel(keycode).is_some() => {
+					if let (false, Some(channel)) = (repeat, keycode_to_channel(keycode)) {
+						toggle_channel(&mut nes, channel);
+					}
+				},
 
-					_ => {}
-				}
+				Event::KeyDown {keycode: Some(Keycode::Backspace), ..} => {
+					rewinding = true;
+				},
+
+				Event::KeyUp {keycode: Some(Keycode::Backspace), ..} => {
+					rewinding = false;
+				},
+
+				Event::KeyDown {keycode: Some(keycode), ..} => {
+					if let Some(button) = keycode_to_button(keycode) {
+						buttons |= button.mask();
+					}
+
+					if let Some(slot) = keycode_to_slot(keycode) {
+						state_slot = slot;
+						println!("Selected state slot {}", slot);
+					}
+				},
+
+				Event::KeyUp {keycode: Some(keycode), ..} => {
+					if let Some(button) = keycode_to_button(keycode) {
+						buttons &= !button.mask();
+					}
+				},
+
+				_ => {}
 			}
+		}
+
+		if rewinding {
+			rewind.rewind_frame(&mut nes);
 
-			sdl_canvas.clear();
-			cpu.draw_screen(&mut sdl_texture);
-			sdl_canvas.copy(&sdl_texture, None, Some(Rect::new(0, -8, 256, 240))).unwrap();
-			sdl_canvas.present();
+			// Replaying up to the snapshot is not meant to be heard.
+			nes.take_samples();
+		} else {
+			nes.set_input(0, buttons);
+			nes.run_frame();
+			rewind.record(&nes);
 
-			//nt_canvas.clear();
-			//cpu.draw_nametables(&mut nt_texture);
-			//nt_canvas.copy(&nt_texture, None, Some(Rect::new(0, 0, 512, 480))).unwrap();
-			//nt_canvas.present();
+			let samples = nes.take_samples();
 
-			end_time = Instant::now();
-			
-			elapsed = end_time.duration_since(start_time);
-			elapsed_ms = (elapsed.as_secs() as f64 * 1000.0) + (elapsed.subsec_nanos() as f64 / 1000000.0);
+			if let Some(queue) = audio_queue.as_ref() {
+				let queued = queued_samples(queue);
+				nes.set_audio_rate_adjust(pacer.rate_adjust(queued));
+
+				// Only drop audio if the queue is far past its target, as it
+				// can be after the window has been dragged.
+				if queued < 4 * pacer.audio_target() {
+					queue.queue(&samples);
+					audio_queued = true;
+				}
+			}
 
-			if elapsed_ms < FRAME_TIME {
-				sleep_time = (FRAME_TIME - elapsed_ms) as u64;
+			if nes.halted() != halted {
+				halted = nes.halted();
 
-				if sleep_time != 0 {
-					thread::sleep(time::Duration::from_millis(sleep_time));
+				if let Some(opcode) = nes.jam_opcode() {
+					eprintln!("rnes: CPU halted by JAM opcode 0x{:02x}", opcode);
 				}
 			}
 
-			start_time = Instant::now();
+			if nes.frame().is_multiple_of(BATTERY_SAVE_INTERVAL) {
+				save_battery_ram(&nes, &battery_path, &mut battery_saved);
+			}
+		}
+
+		sdl_canvas.clear();
+		sdl_texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+			match ntsc_filter.as_mut() {
+				Some(filter) => filter.draw_rgb(nes.framebuffer(), nes.frame_phase(), buffer, pitch),
+				None => ricoh2c02::draw_rgb(nes.framebuffer(), &palette, buffer, pitch),
+			}
+		}).unwrap();
+		sdl_canvas.copy(&sdl_texture, None, Some(Rect::new(0, -8 * scale as i32, 256 * scale, 240 * scale))).unwrap();
+		sdl_canvas.present();
+
+		//nt_canvas.clear();
+		//nt_texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+		//	nes.draw_nametables(buffer, pitch);
+		//}).unwrap();
+		//nt_canvas.copy(&nt_texture, None, Some(Rect::new(0, 0, 512, 480))).unwrap();
+		//nt_canvas.present();
+
+		match audio_queue.as_ref() {
+			Some(queue) if audio_queued => pacer.wait_for_audio(|| queued_samples(queue)),
+			_ => pacer.wait(),
 		}
 	}
-}
\ No newline at end of file
+
+	save_battery_ram(&nes, &battery_path, &mut battery_saved);
+	finish_trace(&mut nes);
+}
@@ -1,27 +1,44 @@
-extern crate sdl2;
-
 use nes::controller::Controller;
+use nes::controller::CONTROLLER_PORT_1;
+use nes::controller::CONTROLLER_PORT_2;
+use nes::controller::CONTROLLER_STROBE;
 use nes::mapper::Mapper;
+use nes::ricoh2a03::apu::Apu;
+use nes::ricoh2a03::apu::APU_FRAME_COUNTER;
+use nes::ricoh2a03::apu::APU_STATUS;
+use nes::ricoh2a03::cpu_bus::CpuBus;
 use nes::ricoh2c02::Ricoh2C02;
-use sdl2::keyboard::*;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub const RAM_SIZE: usize = 0x800;
 
+pub const IRQ_MAPPER: u8 = 0x01;
+pub const IRQ_FRAME_COUNTER: u8 = 0x02;
+pub const IRQ_DMC: u8 = 0x04;
+
 pub struct Bus {
-    controller: Controller,
-    mapper: Rc<RefCell<Box<Mapper+Send>>>,
+    apu: Apu,
+    controllers: [Controller; 2],
+    cycles: u64,
+    irq: u8,
+    mapper: Rc<RefCell<Box<dyn Mapper+Send>>>,
     ppu: Ricoh2C02,
     ram: Box<[u8]>,
 }
 
 impl Bus {
-    pub fn new(mapper: Rc<RefCell<Box<Mapper+Send>>>, ppu: Ricoh2C02) -> Bus {
+    pub fn new(mapper: Rc<RefCell<Box<dyn Mapper+Send>>>, ppu: Ricoh2C02) -> Bus {
         Bus {
-            controller: Controller::new(),
-            mapper: mapper,
-            ppu: ppu,
+            apu: Apu::new(),
+            controllers: [Controller::new(), Controller::new()],
+            cycles: 0,
+            irq: 0,
+            mapper,
+            ppu,
             ram: vec![0; RAM_SIZE].into_boxed_slice(),
         }
     }
@@ -34,11 +51,108 @@ impl Bus {
     //    self.ppu.draw_tiles(texture);
     //}
 
-    pub fn draw_screen(&self, texture: &mut sdl2::render::Texture) {
-        self.ppu.draw_screen(texture);
+    pub fn acknowledge_irq(&mut self, source: u8) {
+        self.irq &= !source;
+    }
+
+    pub fn assert_irq(&mut self, source: u8) {
+        self.irq |= source;
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn framebuffer(&self) -> &[u16] {
+        self.ppu.framebuffer()
+    }
+
+    pub fn frame_phase(&self) -> u8 {
+        self.ppu.frame_phase()
     }
 
-    pub fn read(&mut self, address: u16) -> u8 {
+    pub fn irq_sources(&self) -> u8 {
+        self.irq
+    }
+
+    pub fn ppu_position(&self) -> (isize, usize) {
+        (self.ppu.scanline(), self.ppu.dot())
+    }
+
+    pub fn set_ppu_position(&mut self, scanline: isize, dot: usize) {
+        self.ppu.set_position(scanline, dot);
+    }
+
+    pub fn power_on(&mut self) {
+        for byte in self.ram.iter_mut() {
+            *byte = 0;
+        }
+
+        self.controllers = [Controller::new(), Controller::new()];
+        self.ppu = Ricoh2C02::new(self.mapper.clone());
+        self.apu.power_on();
+        self.cycles = 0;
+        self.irq = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.apu.reset();
+        self.acknowledge_irq(IRQ_FRAME_COUNTER | IRQ_DMC);
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_u64(self.cycles);
+        state.write_u8(self.irq);
+
+        for controller in self.controllers.iter() {
+            controller.save_state(state);
+        }
+
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        self.mapper.borrow().save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_bytes(&mut self.ram)?;
+        self.cycles = state.read_u64()?;
+        self.irq = state.read_u8()?;
+
+        for controller in self.controllers.iter_mut() {
+            controller.load_state(state)?;
+        }
+
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.mapper.borrow_mut().load_state(state)
+    }
+
+    pub fn input(&self, port: usize) -> u8 {
+        self.controllers.get(port).map_or(0, |controller| controller.state())
+    }
+
+    pub fn set_input(&mut self, port: usize, buttons: u8) {
+        // Only two ports exist; input for any other port is dropped.
+        if let Some(controller) = self.controllers.get_mut(port) {
+            controller.set_state(buttons);
+        }
+    }
+
+    pub fn should_redraw(&mut self) -> bool {
+        self.ppu.should_redraw()
+    }
+
+    #[allow(clippy::manual_range_contains)]
+    fn read_memory(&mut self, address: u16) -> u8 {
         if address < 0x2000 {
             return self.ram[address as usize % RAM_SIZE];
         }
@@ -47,8 +161,16 @@ impl Bus {
             return self.ppu.io_read(0x2000 + (address % 8));
         }
 
-        if self.controller.in_range(address) {
-            return self.controller.io_read();
+        if address == APU_STATUS {
+            return self.apu.read_status();
+        }
+
+        if address == CONTROLLER_PORT_1 {
+            return self.controllers[0].io_read();
+        }
+
+        if address == CONTROLLER_PORT_2 {
+            return self.controllers[1].io_read();
         }
 
         let mapper = self.mapper.borrow_mut();
@@ -63,25 +185,113 @@ impl Bus {
         panic!("read from unknown memory region 0x{:04x}", address)
     }
 
-    pub fn set_button(&mut self, keycode: Keycode, state: bool) {
-        self.controller.set_button(keycode, state);
+    // Both DMA paths run their cycles with `Bus::tick` from inside a CPU
+    // access, so the CPU doesn't sample IRQ or NMI until they're over (see
+    // `CpuBus`). Neither line drops back within a DMA in practice: the
+    // PPU holds NMI for the rest of vblank and IRQ sources wait to be
+    // acknowledged.
+    //
+    // The DMC's memory reader can only halt the CPU on a read. The cycle
+    // the CPU was about to read on becomes the halt cycle, followed by a
+    // dummy cycle, an alignment cycle when needed to land on a get cycle,
+    // and the fetch, before the CPU's read goes ahead on a cycle of its own.
+    // The CPU keeps its address on the bus while halted, so a register read
+    // sees its side effects twice, which is how controller reads get
+    // corrupted by DMC playback.
+    fn dmc_dma(&mut self, dmc_address: u16, cpu_address: u16) {
+        if (0x2000..0x4020).contains(&cpu_address) {
+            self.read_memory(cpu_address);
+        }
+
+        self.tick();
+
+        if self.cycles.is_multiple_of(2) {
+            self.tick();
+        }
+
+        self.tick();
+        let value = self.read_memory(dmc_address);
+        self.apu.dmc_fill(value);
+
+        self.tick();
     }
 
-    pub fn should_redraw(&mut self) -> bool {
-        self.ppu.should_redraw()
+    // OAM DMA alternates get and put cycles, so a DMC fetch landing in it
+    // takes over a get cycle and costs one more cycle to realign. Like the
+    // DMC, it starts with a halt cycle and then waits for a get cycle, which
+    // makes it 513 or 514 cycles long depending on CPU cycle parity.
+    fn oam_dma(&mut self, page: u8) {
+        self.tick();
+
+        if self.cycles.is_multiple_of(2) {
+            self.tick();
+        }
+
+        for i in 0..256 {
+            if let Some(dmc_address) = self.apu.dmc_fetch_address() {
+                self.tick();
+                let value = self.read_memory(dmc_address);
+                self.apu.dmc_fill(value);
+                self.tick();
+            }
+
+            let transfer_address = ((page as u16) << 8) | i;
+
+            self.tick();
+            let data = self.read_memory(transfer_address);
+
+            self.tick();
+            self.write(0x2004, data);
+        }
     }
+}
 
-    pub fn should_nmi(&mut self) -> bool {
-        self.ppu.should_nmi()
+impl CpuBus for Bus {
+    fn read(&mut self, address: u16) -> u8 {
+        if let Some(dmc_address) = self.apu.dmc_fetch_address() {
+            self.dmc_dma(dmc_address, address);
+        }
+
+        self.read_memory(address)
     }
 
-    pub fn tick(&mut self) {
+    fn tick(&mut self) {
+        self.cycles += 1;
+
         self.ppu.tick();
         self.ppu.tick();
         self.ppu.tick();
+
+        let (mapper_irq, mapper_audio) = {
+            let mut mapper = self.mapper.borrow_mut();
+            mapper.clock();
+            (mapper.irq(), mapper.audio())
+        };
+
+        if mapper_irq {
+            self.assert_irq(IRQ_MAPPER);
+        } else {
+            self.acknowledge_irq(IRQ_MAPPER);
+        }
+
+        self.apu.set_expansion_audio(mapper_audio);
+        self.apu.tick();
+
+        if self.apu.frame_irq() {
+            self.assert_irq(IRQ_FRAME_COUNTER);
+        } else {
+            self.acknowledge_irq(IRQ_FRAME_COUNTER);
+        }
+
+        if self.apu.dmc_irq() {
+            self.assert_irq(IRQ_DMC);
+        } else {
+            self.acknowledge_irq(IRQ_DMC);
+        }
     }
 
-    pub fn write(&mut self, address: u16, value: u8) {
+    #[allow(clippy::manual_range_contains)]
+    fn write(&mut self, address: u16, value: u8) {
         if address < 0x2000 {
             return self.ram[address as usize % RAM_SIZE] = value;
         }
@@ -94,35 +304,48 @@ impl Bus {
             let mut mapper = self.mapper.borrow_mut();
             if mapper.in_range(address) {
                 return mapper.write_prg(address, value);
+            }
         }
 
+        if (0x4000..=0x4013).contains(&address) || address == APU_STATUS || address == APU_FRAME_COUNTER {
+            return self.apu.write(address, value);
         }
-        if self.controller.in_range(address) {
-            self.controller.io_write(value);
+
+        if address == CONTROLLER_STROBE {
+            self.controllers[0].io_write(value);
+            self.controllers[1].io_write(value);
         }
 
         if address == 0x4014 {
-            for i in 0..256 {
-                let transfer_address = ((value as u16) << 8) | i;
+            self.oam_dma(value);
+        }
 
-                let data = self.read(transfer_address);
-                self.tick();
+        if address >= 0x4000 && address <= 0x4020 {
+            return;
+        }
 
-                self.write(0x2004, data);
-                self.tick();
-            }
+        panic!("write to unknown memory region 0x{:04x}", address)
+    }
 
-            self.tick();
+    fn irq(&self) -> bool {
+        self.irq != 0
+    }
 
-            if self.ppu.odd() {
-                self.tick();
-            }
+    fn nmi(&self) -> bool {
+        self.ppu.nmi()
+    }
+
+    // Registers are not readable without side effects and return 0.
+    fn peek(&self, address: u16) -> u8 {
+        if address < 0x2000 {
+            return self.ram[address as usize % RAM_SIZE];
         }
 
-        if address >= 0x4000 && address <= 0x4020 {
-            return;
+        let mapper = self.mapper.borrow();
+        if mapper.in_range(address) {
+            return mapper.read_prg(address);
         }
 
-        panic!("write to unknown memory region 0x{:04x}", address)
+        0
     }
-}
\ No newline at end of file
+}
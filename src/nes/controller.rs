@@ -1,6 +1,37 @@
-use sdl2::keyboard::*;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 
-pub const CONTROLLER_ADDRESS: u16 = 0x4016;
+pub const CONTROLLER_STROBE: u16 = 0x4016;
+pub const CONTROLLER_PORT_1: u16 = 0x4016;
+pub const CONTROLLER_PORT_2: u16 = 0x4017;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    pub fn mask(self) -> u8 {
+        match self {
+            Button::A       => 0x01,
+            Button::B       => 0x02,
+            Button::Select  => 0x04,
+            Button::Start   => 0x08,
+            Button::Up      => 0x10,
+            Button::Down    => 0x20,
+            Button::Left    => 0x40,
+            Button::Right   => 0x80,
+        }
+    }
+}
 
 pub struct Controller {
     strobe: bool,
@@ -8,6 +39,12 @@ pub struct Controller {
     state_locked: u8,
 }
 
+impl Default for Controller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Controller {
     pub fn new() -> Controller {
         Controller {
@@ -17,10 +54,7 @@ impl Controller {
         }
     }
 
-    pub fn in_range(&self, address: u16) -> bool {
-        return address == CONTROLLER_ADDRESS
-    }
-
+    #[allow(clippy::needless_return)]
     pub fn io_read(&mut self) -> u8 {
         if self.strobe {
             return 0x40 | (self.state & 0x1);
@@ -40,17 +74,24 @@ impl Controller {
         self.strobe = (value & 0x1) != 0;
     }
 
-    pub fn set_button(&mut self, keycode: Keycode, state: bool) {
-        match keycode {
-            Keycode::A      => { self.state &= !0x1; self.state |= (state as u8) << 0 },
-            Keycode::S      => { self.state &= !0x2; self.state |= (state as u8) << 1 },
-            Keycode::Z      => { self.state &= !0x4; self.state |= (state as u8) << 2 },
-            Keycode::X      => { self.state &= !0x8; self.state |= (state as u8) << 3 },
-            Keycode::Up     => { self.state &= !0x10; self.state |= (state as u8) << 4 },
-            Keycode::Down   => { self.state &= !0x20; self.state |= (state as u8) << 5 },
-            Keycode::Left   => { self.state &= !0x40; self.state |= (state as u8) << 6 },
-            Keycode::Right  => { self.state &= !0x80; self.state |= (state as u8) << 7 },
-            _ => (),
-        }
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.strobe);
+        state.write_u8(self.state);
+        state.write_u8(self.state_locked);
     }
-}
\ No newline at end of file
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.strobe = state.read_bool()?;
+        self.state = state.read_u8()?;
+        self.state_locked = state.read_u8()?;
+        Ok(())
+    }
+
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    pub fn set_state(&mut self, buttons: u8) {
+        self.state = buttons;
+    }
+}
@@ -1,8 +1,12 @@
 use nes::rom::MirrorMode;
 use nes::rom::Rom;
+use nes::rom::RomError;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use nes::mappers::cnrom::Cnrom;
-use nes::mappers::nrom::Nrom;
 use nes::mappers::mmc1::Mmc1;
+use nes::mappers::nrom::Nrom;
 use nes::mappers::unrom::Unrom;
 
 pub trait Mapper {
@@ -12,18 +16,43 @@ pub trait Mapper {
     fn read_prg(&self, address: u16) -> u8;
     fn write_chr(&mut self, address: u16, value: u8);
     fn write_prg(&mut self, address: u16, value: u8);
+
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError>;
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn load_battery_ram(&mut self, _: &[u8]) {}
+
+    // Called once per CPU cycle, before that cycle's read or write.
+    fn clock(&mut self) {}
+
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // The output of a sound chip on the cartridge, on the APU mixer's scale
+    // where 1.0 is about as loud as the 2A03 gets.
+    fn audio(&self) -> f32 {
+        0.0
+    }
 }
 
-pub fn create_mapper(rom: Rom) -> Box<Mapper + Send> {
-    let mapper = rom.mapper();
+pub fn copy_battery_ram(ram: &mut [u8], data: &[u8]) {
+    let length = ram.len().min(data.len());
+    ram[..length].copy_from_slice(&data[..length]);
+}
 
-    println!("Mapper #{}", mapper);
+pub fn create_mapper(rom: Rom) -> Result<Box<dyn Mapper + Send>, RomError> {
+    let mapper = rom.mapper();
 
     match mapper {
-        0 => Box::new(Nrom::new(rom)) as Box<Mapper + Send>,
-        //1 => Box::new(Mmc1::new(rom)) as Box<Mapper + Send>,
-        2 => Box::new(Unrom::new(rom)) as Box<Mapper + Send>,
-        3 => Box::new(Cnrom::new(rom)) as Box<Mapper + Send>,
-        _ => panic!("unsupported mapper {}", mapper)
+        0 => Ok(Box::new(Nrom::new(rom)) as Box<dyn Mapper + Send>),
+        1 => Ok(Box::new(Mmc1::new(rom)) as Box<dyn Mapper + Send>),
+        2 => Ok(Box::new(Unrom::new(rom)) as Box<dyn Mapper + Send>),
+        3 => Ok(Box::new(Cnrom::new(rom)) as Box<dyn Mapper + Send>),
+        _ => Err(RomError::UnsupportedMapper(mapper))
     }
 }
\ No newline at end of file
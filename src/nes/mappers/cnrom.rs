@@ -1,6 +1,9 @@
 use nes::mapper::Mapper;
 use nes::rom::MirrorMode;
 use nes::rom::Rom;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use nes::rom::ROM_CHR_BANK_SIZE;
 use nes::rom::ROM_PRG_BANK_SIZE;
 
@@ -10,6 +13,7 @@ pub struct Cnrom {
 }
 
 impl Cnrom {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(rom: Rom) -> Cnrom {
         Cnrom {
             rom: rom,
@@ -23,13 +27,14 @@ impl Mapper for Cnrom {
         self.rom.mirroring()
     }
 
+    #[allow(clippy::needless_return)]
     fn in_range(&self, address: u16) -> bool {
         return address >= 0x4020;
     }
 
     fn read_chr(&self, address: u16) -> u8 {
-        let bank_address = self.chr_bank as usize * ROM_CHR_BANK_SIZE;
-        self.rom.read_chr(bank_address + address as usize)
+        let bank = self.chr_bank as usize % self.rom.chr_banks();
+        self.rom.read_chr(bank * ROM_CHR_BANK_SIZE + address as usize)
     }
 
     fn read_prg(&self, address: u16) -> u8 {
@@ -54,4 +59,18 @@ impl Mapper for Cnrom {
             self.chr_bank = value & 0x03;
         }
     }
-}
\ No newline at end of file
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.chr_bank = state.read_u8()?;
+
+        if self.chr_bank > 0x03 {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
+use nes::mapper::copy_battery_ram;
 use nes::mapper::Mapper;
 use nes::rom::MirrorMode;
 use nes::rom::Rom;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use nes::rom::ROM_PRG_BANK_SIZE;
 
+const CHR_BANK_SIZE: usize = 0x1000;
+
 pub struct Mmc1 {
     rom: Rom,
     prg_ram: Box<[u8]>,
@@ -10,28 +16,88 @@ pub struct Mmc1 {
 
     shift: u8,
     control: u8,
-    prg_bank: u8
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+
+    // CPU cycles since the last write to the serial port, saturating.
+    since_write: u8,
 }
 
 impl Mmc1 {
     pub fn new(rom: Rom) -> Mmc1 {
+        let chr_ram = if rom.chr_banks() == 0 { 0x2000 } else { 0 };
+
         Mmc1 {
-            rom: rom,
+            rom,
             prg_ram: vec![0; 0x2000].into_boxed_slice(),
-            chr_ram: vec![0; 0x2000].into_boxed_slice(),
+            chr_ram: vec![0; chr_ram].into_boxed_slice(),
 
             shift: 0b10000,
-            control: 0xc | 0x3,
-            prg_bank: 0
+            control: 0xc,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+
+            since_write: u8::MAX,
+        }
+    }
+
+    // Bit 4 of the PRG bank register disables the RAM at $6000-$7FFF on
+    // MMC1B and later, which is what battery-backed boards rely on to keep
+    // saves from being corrupted at power off.
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    fn prg_address(&self, bank: usize, address: u16) -> usize {
+        let bank = bank % self.rom.prg_banks();
+        bank * ROM_PRG_BANK_SIZE + (address as usize & (ROM_PRG_BANK_SIZE - 1))
+    }
+
+    fn chr_address(&self, address: u16) -> usize {
+        let bank;
+
+        if self.control & 0x10 == 0 {
+            bank = (self.chr_bank_0 & 0x1e) as usize | (address as usize >> 12);
+        } else if address < 0x1000 {
+            bank = self.chr_bank_0 as usize;
+        } else {
+            bank = self.chr_bank_1 as usize;
+        }
+
+        bank * CHR_BANK_SIZE + (address as usize & (CHR_BANK_SIZE - 1))
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address & 0xe000 {
+            0x8000 => self.control = value,
+            0xa000 => self.chr_bank_0 = value,
+            0xc000 => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
         }
     }
 }
 
 impl Mapper for Mmc1 {
+    fn battery_ram(&self) -> Option<&[u8]> {
+        if self.rom.has_battery() {
+            Some(&self.prg_ram)
+        } else {
+            None
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        if self.rom.has_battery() {
+            copy_battery_ram(&mut self.prg_ram, data);
+        }
+    }
+
     fn mirroring(&self) -> MirrorMode {
         match self.control & 0x03 {
             0 => MirrorMode::OneScreen,
-            1 => MirrorMode::OneScreen,
+            1 => MirrorMode::OneScreenUpper,
             2 => MirrorMode::Vertical,
             3 => MirrorMode::Horizontal,
             _ => unreachable!()
@@ -39,11 +105,21 @@ impl Mapper for Mmc1 {
     }
 
     fn in_range(&self, address: u16) -> bool {
-        return address >= 0x4020;
+        address >= 0x4020
+    }
+
+    fn clock(&mut self) {
+        self.since_write = self.since_write.saturating_add(1);
     }
 
     fn read_chr(&self, address: u16) -> u8 {
-        self.chr_ram[address as usize]
+        let chr_address = self.chr_address(address);
+
+        if self.rom.chr_banks() == 0 {
+            self.chr_ram[chr_address % self.chr_ram.len()]
+        } else {
+            self.rom.read_chr(chr_address % self.rom.chr_rom_size())
+        }
     }
 
     fn read_prg(&self, address: u16) -> u8 {
@@ -52,69 +128,98 @@ impl Mapper for Mmc1 {
         }
 
         if address < 0x8000 {
+            if !self.prg_ram_enabled() {
+                return 0xff;
+            }
+
             return self.prg_ram[address as usize - 0x6000]
         }
 
-        let prg_address = address - 0x8000;
-
-        if self.control & 0xc == 0x0 || self.control & 0xc == 0x4 {
-            let bank = self.prg_bank & 0x0e;
-
-            return self.rom.read_prg(prg_address as usize + (bank as usize * ROM_PRG_BANK_SIZE));
-        } 
+        let bank = (self.prg_bank & 0x0f) as usize;
 
-        if self.control & 0xc == 0x8 {
-            if address < 0xc000 {
-                return self.rom.read_prg(prg_address as usize);
-            } else {
-                return self.rom.read_prg((prg_address as usize - ROM_PRG_BANK_SIZE) +
-                self.prg_bank as usize * ROM_PRG_BANK_SIZE);
-            }
-        } 
-
-        if self.control & 0xc == 0xc {
-            if address < 0xc000 {
-                return self.rom.read_prg(prg_address as usize +
-                self.prg_bank as usize * ROM_PRG_BANK_SIZE);
-            } else {
-                return self.rom.read_prg((prg_address as usize - ROM_PRG_BANK_SIZE) +
-                (self.rom.prg_banks() - 1) * ROM_PRG_BANK_SIZE);
-            }
-        } 
-
-        panic!("unavailable mmc1 configuration! {}", (self.control & 0xc) >> 2)
+        match (self.control >> 2) & 0x03 {
+            0 | 1 => self.rom.read_prg(self.prg_address((bank & 0x0e) | ((address as usize - 0x8000) >> 14), address)),
+            2 if address < 0xc000 => self.rom.read_prg(self.prg_address(0, address)),
+            2 => self.rom.read_prg(self.prg_address(bank, address)),
+            _ if address < 0xc000 => self.rom.read_prg(self.prg_address(bank, address)),
+            _ => self.rom.read_prg(self.prg_address(self.rom.prg_banks() - 1, address)),
+        }
     }
 
     fn write_chr(&mut self, address: u16, value: u8) {
-        self.chr_ram[address as usize] = value;
+        if self.rom.chr_banks() == 0 {
+            let chr_address = self.chr_address(address) % self.chr_ram.len();
+            self.chr_ram[chr_address] = value;
+        }
     }
 
     fn write_prg(&mut self, address: u16, value: u8) {
         if address < 0x6000 {
-            println!("unsupported write to PRG 0x{:04x}", address)
+            println!("unsupported write to PRG 0x{:04x}", address);
+            return;
         }
 
         if address < 0x8000 {
-            self.prg_ram[address as usize - 0x6000] = value
+            if self.prg_ram_enabled() {
+                self.prg_ram[address as usize - 0x6000] = value;
+            }
+            return;
+        }
+
+        // The MMC1 ignores a write on the cycle straight after another, which
+        // is what keeps the double write of INC and friends from shifting in
+        // two bits. Some games (Bill & Ted's Excellent Adventure) rely on it.
+        let consecutive = self.since_write == 1;
+        self.since_write = 0;
+
+        if consecutive {
+            return;
         }
 
         if value & 0x80 != 0 {
             self.shift = 0b10000;
             self.control |= 0x0c;
+            return;
         }
 
         let data = ((value & 0x1) << 4) | (self.shift >> 1);
 
         if self.shift & 0x1 == 0x1 {
-            if address < 0xa000 {
-                self.control = data; 
-            } else if address >= 0xe000 {
-                self.prg_bank = data;
-            }
-
+            self.write_register(address, data);
             self.shift = 0b10000;
         } else {
             self.shift = data;
         }
     }
-}
\ No newline at end of file
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.prg_ram);
+        state.write_bytes(&self.chr_ram);
+        state.write_u8(self.shift);
+        state.write_u8(self.control);
+        state.write_u8(self.chr_bank_0);
+        state.write_u8(self.chr_bank_1);
+        state.write_u8(self.prg_bank);
+        state.write_u8(self.since_write);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_bytes(&mut self.prg_ram)?;
+        state.read_bytes(&mut self.chr_ram)?;
+        self.shift = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.chr_bank_0 = state.read_u8()?;
+        self.chr_bank_1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        self.since_write = state.read_u8()?;
+
+        // The registers are five bits wide, and the shift register always
+        // holds the marker bit that says when it is full.
+        if self.shift == 0 || self.shift > 0x1f || self.control > 0x1f
+        || self.chr_bank_0 > 0x1f || self.chr_bank_1 > 0x1f || self.prg_bank > 0x1f {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
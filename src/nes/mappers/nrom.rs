@@ -1,6 +1,10 @@
+use nes::mapper::copy_battery_ram;
 use nes::mapper::Mapper;
 use nes::rom::MirrorMode;
 use nes::rom::Rom;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use nes::rom::ROM_PRG_BANK_SIZE;
 
 pub struct Nrom {
@@ -10,6 +14,7 @@ pub struct Nrom {
 }
 
 impl Nrom {
+    #[allow(clippy::needless_late_init, clippy::redundant_field_names)]
     pub fn new(rom: Rom) -> Nrom {
         let chr_ram;
 
@@ -28,10 +33,25 @@ impl Nrom {
 }
 
 impl Mapper for Nrom {
+    fn battery_ram(&self) -> Option<&[u8]> {
+        if self.rom.has_battery() {
+            Some(&self.ram)
+        } else {
+            None
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        if self.rom.has_battery() {
+            copy_battery_ram(&mut self.ram, data);
+        }
+    }
+
     fn mirroring(&self) -> MirrorMode {
         self.rom.mirroring()
     }
 
+    #[allow(clippy::needless_return)]
     fn in_range(&self, address: u16) -> bool {
         return address >= 0x4020;
     }
@@ -44,6 +64,7 @@ impl Mapper for Nrom {
         }
     }
 
+    #[allow(clippy::identity_op, clippy::needless_return)]
     fn read_prg(&self, address: u16) -> u8 {
         if address < 0x6000 {
             return 0xff;
@@ -70,6 +91,7 @@ impl Mapper for Nrom {
         }
     }
 
+    #[allow(clippy::manual_range_contains)]
     fn write_prg(&mut self, address: u16, value: u8) {
         if address >= 0x6000 && address < 0x8000 {
             self.ram[address as usize - 0x6000] = value;
@@ -78,4 +100,14 @@ impl Mapper for Nrom {
 
         println!("unsupported write to PRG 0x{:04x}", address)
     }
-}
\ No newline at end of file
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bytes(&self.chr_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_bytes(&mut self.ram)?;
+        state.read_bytes(&mut self.chr_ram)
+    }
+}
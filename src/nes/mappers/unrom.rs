@@ -1,6 +1,9 @@
 use nes::mapper::Mapper;
 use nes::rom::MirrorMode;
 use nes::rom::Rom;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use nes::rom::ROM_PRG_BANK_SIZE;
 
 pub struct Unrom {
@@ -10,6 +13,7 @@ pub struct Unrom {
 }
 
 impl Unrom {
+    #[allow(clippy::redundant_field_names)]
     pub fn new(rom: Rom) -> Unrom {
         Unrom {
             rom: rom,
@@ -24,6 +28,7 @@ impl Mapper for Unrom {
         self.rom.mirroring()
     }
 
+    #[allow(clippy::needless_return)]
     fn in_range(&self, address: u16) -> bool {
         return address >= 0x4020;
     }
@@ -32,6 +37,7 @@ impl Mapper for Unrom {
         self.chr_ram[address as usize]
     }
 
+    #[allow(clippy::needless_return)]
     fn read_prg(&self, address: u16) -> u8 {
         if address < 0x8000 {
             return 0xff;
@@ -39,7 +45,7 @@ impl Mapper for Unrom {
 
         if address < 0xc000 {
             let prg_address = (address - 0x8000) as usize;
-            let bank_offset = self.prg_bank as usize * ROM_PRG_BANK_SIZE;
+            let bank_offset = (self.prg_bank as usize % self.rom.prg_banks()) * ROM_PRG_BANK_SIZE;
             return self.rom.read_prg(bank_offset + prg_address);
         } else {
             let prg_address = (address - 0xc000) as usize;
@@ -59,4 +65,20 @@ impl Mapper for Unrom {
 
         self.prg_bank = value & 0x0f;
     }
-}
\ No newline at end of file
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.chr_ram);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_bytes(&mut self.chr_ram)?;
+        self.prg_bank = state.read_u8()?;
+
+        if self.prg_bank > 0x0f {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
@@ -2,6 +2,252 @@ pub mod bus;
 pub mod controller;
 pub mod mapper;
 pub mod mappers;
+pub mod nestest;
 pub mod ricoh2a03;
 pub mod ricoh2c02;
-pub mod rom;
\ No newline at end of file
+pub mod rewind;
+pub mod rom;
+pub mod state;
+pub mod test_rom;
+pub mod tracer;
+
+use nes::bus::Bus;
+use nes::mapper::create_mapper;
+use nes::mapper::Mapper;
+use nes::ricoh2a03::apu::filter::AudioProfile;
+use nes::ricoh2a03::apu::mixer::Channel;
+use nes::ricoh2a03::apu::mixer::ChannelSettings;
+use nes::ricoh2a03::cpu_bus::CpuBus;
+use nes::ricoh2a03::Ricoh2A03;
+use nes::ricoh2c02::palette::Palette;
+use nes::ricoh2c02::Ricoh2C02;
+use nes::rom::Rom;
+use nes::rom::RomError;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+use nes::tracer::Tracer;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+pub struct Frame<'a> {
+    pub framebuffer: &'a [u16],
+    pub cycles: u64,
+    pub number: u64,
+    pub halted: bool,
+    // Colour subcarrier phase at the start of the frame, for NtscFilter.
+    pub phase: u8,
+}
+
+impl<'a> Frame<'a> {
+    pub fn draw_rgb(&self, palette: &Palette, buffer: &mut [u8], pitch: usize) {
+        ricoh2c02::draw_rgb(self.framebuffer, palette, buffer, pitch);
+    }
+}
+
+pub struct Nes {
+    cpu: Ricoh2A03,
+    mapper: Rc<RefCell<Box<dyn Mapper+Send>>>,
+    rom_hash: u64,
+    frame: u64,
+    tracer: Option<Tracer>,
+}
+
+impl Nes {
+    pub fn new(rom: Rom) -> Result<Nes, RomError> {
+        let rom_hash = rom.hash();
+        let mapper = Rc::new(RefCell::new(create_mapper(rom)?));
+
+        let ppu = Ricoh2C02::new(mapper.clone());
+        let bus = Bus::new(mapper.clone(), ppu);
+
+        Ok(Nes {
+            cpu: Ricoh2A03::new(bus),
+            mapper,
+            rom_hash,
+            frame: 0,
+            tracer: None,
+        })
+    }
+
+    pub fn power_on(&mut self) {
+        self.cpu.power_on();
+        self.frame = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.bus_mut().reset();
+        self.cpu.reset();
+    }
+
+    // Audio is only generated once a sample rate has been set. Samples are
+    // centred on 0.0 after the console's high-pass filters, and interleaved
+    // left and right while `stereo_audio` is true.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.cpu.bus_mut().apu_mut().set_sample_rate(sample_rate);
+    }
+
+    // Stretches the audio by up to 1% either way, for dynamic rate control.
+    pub fn set_audio_rate_adjust(&mut self, ratio: f64) {
+        self.cpu.bus_mut().apu_mut().set_rate_adjust(ratio);
+    }
+
+    pub fn set_audio_profile(&mut self, profile: AudioProfile) {
+        self.cpu.bus_mut().apu_mut().set_audio_profile(profile);
+    }
+
+    pub fn channel_settings(&self, channel: Channel) -> ChannelSettings {
+        self.cpu.bus().apu().channel_settings(channel)
+    }
+
+    pub fn set_channel_settings(&mut self, channel: Channel, settings: ChannelSettings) {
+        self.cpu.bus_mut().apu_mut().set_channel_settings(channel, settings);
+    }
+
+    pub fn stereo_audio(&self) -> bool {
+        self.cpu.bus().apu().stereo()
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        self.cpu.bus_mut().apu_mut().take_samples()
+    }
+
+    pub fn battery_ram(&self) -> Option<Vec<u8>> {
+        self.mapper.borrow().battery_ram().map(|ram| ram.to_vec())
+    }
+
+    pub fn load_battery_ram(&mut self, data: &[u8]) {
+        self.mapper.borrow_mut().load_battery_ram(data);
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cpu.cycles()
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn framebuffer(&self) -> &[u16] {
+        self.cpu.framebuffer()
+    }
+
+    pub fn frame_phase(&self) -> u8 {
+        self.cpu.bus().frame_phase()
+    }
+
+    pub fn halted(&self) -> bool {
+        self.cpu.halted()
+    }
+
+    pub fn jam_opcode(&self) -> Option<u8> {
+        self.cpu.jam_opcode()
+    }
+
+    // Reads CPU address space without side effects. Only RAM and the
+    // cartridge are visible; registers read as zero.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.cpu.bus().peek(address)
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) -> u64 {
+        let start = self.cpu.cycles();
+
+        while self.cpu.cycles() - start < cycles {
+            self.step();
+        }
+
+        self.cpu.cycles() - start
+    }
+
+    pub fn run_frame(&mut self) -> Frame<'_> {
+        let start = self.cpu.cycles();
+        let frame = self.frame;
+
+        while self.frame == frame {
+            self.step();
+        }
+
+        Frame {
+            framebuffer: self.cpu.framebuffer(),
+            cycles: self.cpu.cycles() - start,
+            number: self.frame,
+            halted: self.cpu.halted(),
+            phase: self.cpu.bus().frame_phase(),
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+
+        state::write_header(&mut state, self.rom_hash);
+        state.write_u64(self.frame);
+        self.cpu.save_state(&mut state);
+
+        state.into_inner()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        // Keep a copy of the current machine so that a bad snapshot cannot
+        // leave it half-restored.
+        let backup = self.save_state();
+
+        if let Err(e) = self.restore_state(data) {
+            self.restore_state(&backup).unwrap();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data);
+
+        state::read_header(&mut state, self.rom_hash)?;
+        self.frame = state.read_u64()?;
+        self.cpu.load_state(&mut state)?;
+
+        if !state.is_empty() {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.flush();
+        }
+
+        self.tracer = tracer;
+    }
+
+    pub fn tracer(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    pub fn input(&self, port: usize) -> u8 {
+        self.cpu.bus().input(port)
+    }
+
+    pub fn set_input(&mut self, port: usize, buttons: u8) {
+        self.cpu.set_input(port, buttons);
+    }
+
+    fn step(&mut self) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            if self.cpu.at_instruction() {
+                tracer.trace(&self.cpu, self.frame);
+            }
+        }
+
+        self.cpu.step();
+
+        if self.cpu.should_redraw() {
+            self.frame += 1;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use nes::Nes;
+use std::collections::VecDeque;
+
+// nestest.nes runs its whole suite without the PPU when started at $C000
+// rather than through the reset vector. It keeps the first failing official
+// and unofficial test numbers at $02 and $03 (zero means everything passed)
+// and finishes with the RTS at $C66E, the last line of nestest.log.
+pub const NESTEST_START: u16 = 0xc000;
+pub const NESTEST_END: u16 = 0xc66e;
+pub const NESTEST_OFFICIAL_RESULT: u16 = 0x0002;
+pub const NESTEST_UNOFFICIAL_RESULT: u16 = 0x0003;
+
+// Far more than the ~9000 instructions the suite takes.
+const INSTRUCTION_LIMIT: u64 = 100_000;
+
+// Lines shown before a divergence.
+const CONTEXT_LINES: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    // 1-based line number in the reference log.
+    pub line: usize,
+    pub context: Vec<String>,
+    // None if the reference ran out first.
+    pub expected: Option<String>,
+    // None if the run stopped first.
+    pub actual: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NestestResult {
+    pub official: u8,
+    pub unofficial: u8,
+    pub instructions: u64,
+    // Whether the run got to $C66E rather than halting or hitting the limit.
+    pub finished: bool,
+    pub divergence: Option<Divergence>,
+}
+
+impl NestestResult {
+    pub fn passed(&self) -> bool {
+        self.finished && self.official == 0 && self.unofficial == 0 && self.divergence.is_none()
+    }
+}
+
+// Powers the machine on, moves the CPU to $C000 in the state nestest.log
+// starts from and runs the suite, comparing each traced instruction with the
+// matching line of `reference` if one is given. Stops at the first divergence.
+pub fn run_nestest(nes: &mut Nes, reference: Option<&str>) -> NestestResult {
+    nes.power_on();
+    nes.cpu.set_pc(NESTEST_START);
+
+    // Nintendulator powers on at the top of scanline 0 rather than on the
+    // pre-render line; the reset sequence has already moved the dot along.
+    let (_, dot) = nes.cpu.bus().ppu_position();
+    nes.cpu.bus_mut().set_ppu_position(0, dot);
+
+    let mut expected_lines = reference.map(|log| log.lines().map(str::trim_end));
+    let mut context = VecDeque::with_capacity(CONTEXT_LINES);
+    let mut instructions = 0;
+    let mut finished = false;
+    let mut divergence = None;
+
+    while !nes.halted() && instructions < INSTRUCTION_LIMIT {
+        if !nes.cpu.at_instruction() {
+            nes.step();
+            continue;
+        }
+
+        let actual = nes.cpu.trace();
+        let pc = nes.cpu.pc();
+        instructions += 1;
+
+        if let Some(lines) = expected_lines.as_mut() {
+            let expected = lines.next();
+
+            if expected != Some(actual.as_str()) {
+                divergence = Some(Divergence {
+                    line: instructions as usize,
+                    context: context.drain(..).collect(),
+                    expected: expected.map(str::to_string),
+                    actual: Some(actual),
+                });
+                break;
+            }
+
+            if context.len() == CONTEXT_LINES {
+                context.pop_front();
+            }
+            context.push_back(actual);
+        }
+
+        nes.step();
+
+        if pc == NESTEST_END {
+            finished = true;
+            break;
+        }
+    }
+
+    // A log that goes on past where the run stopped is a divergence too.
+    if divergence.is_none() {
+        if let Some(expected) = expected_lines.as_mut().and_then(|lines| lines.find(|line| !line.is_empty())) {
+            divergence = Some(Divergence {
+                line: instructions as usize + 1,
+                context: context.into_iter().collect(),
+                expected: Some(expected.to_string()),
+                actual: None,
+            });
+        }
+    }
+
+    NestestResult {
+        official: nes.peek(NESTEST_OFFICIAL_RESULT),
+        unofficial: nes.peek(NESTEST_UNOFFICIAL_RESULT),
+        instructions,
+        finished,
+        divergence,
+    }
+}
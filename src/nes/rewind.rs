@@ -0,0 +1,250 @@
+use nes::Nes;
+use std::collections::VecDeque;
+
+pub const REWIND_DEFAULT_INTERVAL: u64 = 5;
+pub const REWIND_DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+// Older snapshots are kept as the XOR of themselves with the next newer
+// snapshot. Consecutive machine states differ in very few bytes, so the XOR is
+// almost entirely zero and run-length encodes well.
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut i = 0;
+
+    while i < older.len() {
+        let zero_start = i;
+        while i < older.len() && older[i] == newer[i] {
+            i += 1;
+        }
+
+        let literal_start = i;
+        while i < older.len() && older[i] != newer[i] {
+            i += 1;
+        }
+
+        write_varint(&mut delta, literal_start - zero_start);
+        write_varint(&mut delta, i - literal_start);
+
+        for j in literal_start..i {
+            delta.push(older[j] ^ newer[j]);
+        }
+    }
+
+    delta
+}
+
+fn decode_delta(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut older = newer.to_vec();
+    let mut position = 0;
+    let mut i = 0;
+
+    while i < delta.len() {
+        position += read_varint(delta, &mut i);
+
+        let literals = read_varint(delta, &mut i);
+        for _ in 0..literals {
+            older[position] ^= delta[i];
+            position += 1;
+            i += 1;
+        }
+    }
+
+    older
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        data.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    data.push(value as u8);
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = data[*i];
+        *i += 1;
+
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return value;
+        }
+    }
+}
+
+pub struct Rewind {
+    interval: u64,
+    budget: usize,
+
+    latest: Option<(u64, Vec<u8>)>,
+    deltas: VecDeque<(u64, Vec<u8>)>,
+    deltas_size: usize,
+
+    // The buttons held on each frame since the oldest snapshot, so that the
+    // frames between snapshots are re-emulated with the input they had.
+    inputs: VecDeque<(u64, [u8; 2])>,
+
+    playback: Vec<Vec<u8>>,
+}
+
+impl Rewind {
+    pub fn new(interval: u64, budget: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+
+            latest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+
+            inputs: VecDeque::new(),
+
+            playback: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.deltas_size = 0;
+        self.inputs.clear();
+        self.playback.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none() && self.playback.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.latest.iter().count() + self.deltas.len()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        let latest = self.latest.as_ref().map_or(0, |(_, state)| state.len());
+        latest + self.deltas_size
+    }
+
+    pub fn record(&mut self, nes: &Nes) {
+        self.playback.clear();
+
+        // After a rewind the frames past this one belong to the abandoned
+        // timeline.
+        while let Some(&(frame, _)) = self.inputs.back() {
+            if frame < nes.frame() {
+                break;
+            }
+            self.inputs.pop_back();
+        }
+
+        self.inputs.push_back((nes.frame(), [nes.input(0), nes.input(1)]));
+
+        if !nes.frame().is_multiple_of(self.interval) {
+            return;
+        }
+
+        self.push(nes.frame(), nes.save_state());
+    }
+
+    fn push(&mut self, frame: u64, state: Vec<u8>) {
+        if let Some((latest_frame, latest)) = self.latest.take() {
+            if latest.len() == state.len() && latest_frame < frame {
+                let delta = encode_delta(&latest, &state);
+                self.deltas_size += delta.len();
+                self.deltas.push_back((latest_frame, delta));
+            } else {
+                self.deltas.clear();
+                self.deltas_size = 0;
+            }
+        }
+
+        self.latest = Some((frame, state));
+
+        while self.memory_used() > self.budget {
+            match self.deltas.pop_front() {
+                Some((_, delta)) => self.deltas_size -= delta.len(),
+                None => break,
+            }
+        }
+
+        let oldest = match self.deltas.front() {
+            Some(&(frame, _)) => frame,
+            None => frame,
+        };
+
+        while let Some(&(frame, _)) = self.inputs.front() {
+            if frame > oldest {
+                break;
+            }
+            self.inputs.pop_front();
+        }
+    }
+
+    fn input(&self, frame: u64) -> Option<[u8; 2]> {
+        self.inputs.iter().rev()
+            .find(|&&(input_frame, _)| input_frame == frame)
+            .map(|&(_, input)| input)
+    }
+
+    fn pop(&mut self) -> Option<(u64, Vec<u8>)> {
+        let (frame, state) = self.latest.take()?;
+
+        if let Some((older_frame, delta)) = self.deltas.pop_back() {
+            self.deltas_size -= delta.len();
+            self.latest = Some((older_frame, decode_delta(&state, &delta)));
+        }
+
+        Some((frame, state))
+    }
+
+    // Steps the machine back by one frame, returning false once the history is
+    // exhausted. Snapshots are only taken every `interval` frames, so the frames
+    // in between are re-emulated from the older snapshot, with the input that
+    // was recorded for them, and then played back newest first.
+    pub fn rewind_frame(&mut self, nes: &mut Nes) -> bool {
+        if self.playback.is_empty() {
+            let target = nes.frame();
+
+            let (frame, state) = loop {
+                match self.pop() {
+                    Some((frame, state)) => {
+                        if frame < target {
+                            break (frame, state);
+                        }
+                    },
+                    None => return false,
+                }
+            };
+
+            if nes.load_state(&state).is_err() {
+                self.clear();
+                return false;
+            }
+
+            // Keep the snapshot in the history so that resuming part way
+            // through the playback does not leave a gap.
+            self.push(frame, state.clone());
+            self.playback.push(state);
+
+            while nes.frame() + 1 < target {
+                if let Some(input) = self.input(nes.frame() + 1) {
+                    nes.set_input(0, input[0]);
+                    nes.set_input(1, input[1]);
+                }
+
+                nes.run_frame();
+                self.playback.push(nes.save_state());
+            }
+        }
+
+        match self.playback.pop() {
+            Some(state) => nes.load_state(&state).is_ok(),
+            None => false,
+        }
+    }
+}
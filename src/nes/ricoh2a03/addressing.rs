@@ -1,6 +1,10 @@
+use nes::ricoh2a03::cpu_bus::CpuBus;
+use nes::ricoh2a03::opcodes::Class;
+use nes::ricoh2a03::opcodes::Mode;
+use nes::ricoh2a03::opcodes::Opcode;
 use nes::ricoh2a03::Ricoh2A03;
 
-impl Ricoh2A03 {
+impl<B: CpuBus> Ricoh2A03<B> {
     pub fn imm(&mut self) -> u16 {
 		self.pc = self.pc.wrapping_add(1);
 		self.pc.wrapping_sub(1)
@@ -17,13 +21,15 @@ impl Ricoh2A03 {
 	}
 
 	pub fn zpx(&mut self) -> u16 {
-		self.tick();
-		(self.zp().wrapping_add(self.x as u16)) % 0x100
+		let base = self.zp();
+		self.read8(base);
+		(base.wrapping_add(self.x as u16)) % 0x100
 	}
 
 	pub fn zpy(&mut self) -> u16 {
-		self.tick();
-		(self.zp().wrapping_add(self.y as u16)) % 0x100
+		let base = self.zp();
+		self.read8(base);
+		(base.wrapping_add(self.y as u16)) % 0x100
 	}
 
 	pub fn abs(&mut self) -> u16 {
@@ -31,25 +37,43 @@ impl Ricoh2A03 {
 		self.read16(imm)
 	}	
 
+	// Indexing only adds to the low byte of the address in the first cycle,
+	// so the CPU reads from the wrong page before fixing up the high byte.
+	// Reads skip that cycle when no carry was needed; writes and
+	// read-modify-writes always spend it.
+	fn index(&mut self, base: u16, index: u8, always: bool) -> u16 {
+		let index = index as u16;
+		let address = base.wrapping_add(index);
+
+		if always || self.cross(base, index) {
+			self.read8((base & 0xff00) | (address & 0x00ff));
+		}
+
+		address
+	}
+
 	pub fn _absx(&mut self) -> u16 {
-		self.tick();
 		let a = self.abs();
-		let x = self.x as u16;
-		a.wrapping_add(x)
+		let x = self.x;
+		self.index(a, x, true)
 	}	
 
 	pub fn absx(&mut self) -> u16 {
 		let a = self.abs();
-		let x = self.x as u16;
-		if self.cross(a, x) { self.tick(); }
-		a.wrapping_add(x)
+		let x = self.x;
+		self.index(a, x, false)
+	}	
+
+	pub fn _absy(&mut self) -> u16 {
+		let a = self.abs();
+		let y = self.y;
+		self.index(a, y, true)
 	}	
 
 	pub fn absy(&mut self) -> u16 {
 		let a = self.abs();
-		let y = self.y as u16;
-		if self.cross(a, y) { self.tick(); }
-		a.wrapping_add(y)
+		let y = self.y;
+		self.index(a, y, false)
 	}	
 
 	pub fn indx(&mut self) -> u16 {
@@ -57,17 +81,51 @@ impl Ricoh2A03 {
 		self.read16_d(i, i.wrapping_add(1) % 0x100)
 	}
 
-	pub fn _indy(&mut self) -> u16 {
+	pub fn indy_base(&mut self) -> u16 {
 		let i = self.zp();
-		let y = self.y as u16;
-		self.read16_d(i, i.wrapping_add(1) % 0x100).wrapping_add(y)
+		self.read16_d(i, i.wrapping_add(1) % 0x100)
+	}
+
+	pub fn _indy(&mut self) -> u16 {
+		let a = self.indy_base();
+		let y = self.y;
+		self.index(a, y, true)
 	}
 
 	pub fn indy(&mut self) -> u16 {
-		let i = self.zp();
-		let y = self.y as u16;
-		let a = self.read16_d(i, i.wrapping_add(1) % 0x100).wrapping_add(y);
-		if self.cross(a.wrapping_sub(y), y) { self.tick(); }
-		a
+		let a = self.indy_base();
+		let y = self.y;
+		self.index(a, y, false)
+	}
+
+	// Runs the addressing cycles for an opcode and returns its effective
+	// address.
+	pub fn address(&mut self, opcode: &Opcode) -> u16 {
+		let always = opcode.class != Class::Read;
+
+		match opcode.mode {
+			Mode::Immediate => self.imm(),
+			Mode::ZeroPage => self.zp(),
+			Mode::ZeroPageX => self.zpx(),
+			Mode::ZeroPageY => self.zpy(),
+			Mode::Absolute => self.abs(),
+			Mode::AbsoluteX if always => self._absx(),
+			Mode::AbsoluteX => self.absx(),
+			Mode::AbsoluteY if always => self._absy(),
+			Mode::AbsoluteY => self.absy(),
+			Mode::IndirectX => self.indx(),
+			Mode::IndirectY if always => self._indy(),
+			Mode::IndirectY => self.indy(),
+			_ => unreachable!("{:?} has no effective address", opcode.mode),
+		}
+	}
+
+	// The unindexed base address, for the SH* family which does its own
+	// indexing.
+	pub fn base(&mut self, opcode: &Opcode) -> u16 {
+		match opcode.mode {
+			Mode::IndirectY => self.indy_base(),
+			_ => self.abs(),
+		}
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,99 @@
+use std::f64::consts::PI;
+
+const KERNEL_WIDTH: usize = 16;
+const KERNEL_PHASES: usize = 64;
+// Cutoff as a fraction of the sample rate, a little under Nyquist.
+const KERNEL_CUTOFF: f64 = 0.45;
+// How far `set_rates` may raise the sample rate without a bigger buffer.
+const RATE_HEADROOM: f64 = 1.02;
+
+// Band-limited step synthesis. Changes in amplitude are recorded as deltas
+// at clock times, each spread over a few samples by a windowed sinc picked
+// for its sub-sample position, and the samples are the running sum. Square
+// waves come out without the aliasing that point sampling them produces.
+pub struct BlipBuffer {
+    samples_per_clock: f64,
+    // Fractional sample position of the current frame's first clock.
+    offset: f64,
+    kernels: Vec<[f32; KERNEL_WIDTH]>,
+    deltas: Vec<f32>,
+    sum: f32,
+}
+
+fn kernel(phase: f64) -> [f32; KERNEL_WIDTH] {
+    let mut kernel = [0.0; KERNEL_WIDTH];
+    let half = (KERNEL_WIDTH / 2) as f64;
+
+    for (i, tap) in kernel.iter_mut().enumerate() {
+        let x = i as f64 - half - phase;
+
+        let sinc = if x == 0.0 {
+            2.0 * KERNEL_CUTOFF
+        } else {
+            (2.0 * PI * KERNEL_CUTOFF * x).sin() / (PI * x)
+        };
+
+        let window = 0.42 + 0.5 * (PI * x / half).cos() + 0.08 * (2.0 * PI * x / half).cos();
+        *tap = (sinc * window) as f32;
+    }
+
+    // Each step must add exactly its delta once integrated.
+    let total: f32 = kernel.iter().sum();
+    for tap in kernel.iter_mut() {
+        *tap /= total;
+    }
+
+    kernel
+}
+
+impl BlipBuffer {
+    // `max_clocks` is the longest frame that will be passed to `end_frame`.
+    pub fn new(clock_rate: f64, sample_rate: f64, max_clocks: u32) -> BlipBuffer {
+        let samples_per_clock = sample_rate / clock_rate;
+        let length = (max_clocks as f64 * samples_per_clock * RATE_HEADROOM).ceil() as usize + KERNEL_WIDTH + 1;
+
+        BlipBuffer {
+            samples_per_clock,
+            offset: 0.0,
+            kernels: (0..KERNEL_PHASES).map(|phase| kernel(phase as f64 / KERNEL_PHASES as f64)).collect(),
+            deltas: vec![0.0; length],
+            sum: 0.0,
+        }
+    }
+
+    // Changes the ratio for the following frames, within a couple of percent
+    // of the rates the buffer was made for.
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.samples_per_clock = sample_rate / clock_rate;
+    }
+
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + clock as f64 * self.samples_per_clock;
+        let whole = position as usize;
+        let phase = ((position - whole as f64) * KERNEL_PHASES as f64) as usize;
+
+        let taps = self.deltas[whole..whole + KERNEL_WIDTH].iter_mut();
+        for (sample, tap) in taps.zip(self.kernels[phase].iter()) {
+            *sample += delta * tap;
+        }
+    }
+
+    // Finishes a frame `clocks` long and appends the samples it completed.
+    pub fn end_frame(&mut self, clocks: u32, samples: &mut Vec<f32>) {
+        let end = self.offset + clocks as f64 * self.samples_per_clock;
+        let count = end as usize;
+        self.offset = end - count as f64;
+
+        for delta in self.deltas[..count].iter() {
+            self.sum += delta;
+            samples.push(self.sum);
+        }
+
+        let length = self.deltas.len();
+        self.deltas.copy_within(count.., 0);
+
+        for delta in self.deltas[length - count..].iter_mut() {
+            *delta = 0.0;
+        }
+    }
+}
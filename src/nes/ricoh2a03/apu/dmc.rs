@@ -0,0 +1,208 @@
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+
+// NTSC output rates in CPU cycles per bit.
+static RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+// Plays 1-bit delta encoded samples from $8000-$FFFF. The bytes themselves
+// are fetched by the bus, which sees a request through `fetch_address`.
+pub struct Dmc {
+    irq_enabled: bool,
+    irq: bool,
+    looping: bool,
+    period: u16,
+    timer: u16,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    buffer: Option<u8>,
+
+    shift: u8,
+    bits_remaining: u8,
+    silence: bool,
+    level: u8,
+}
+
+impl Dmc {
+    pub fn new() -> Dmc {
+        Dmc {
+            irq_enabled: false,
+            irq: false,
+            looping: false,
+            period: RATE_TABLE[0],
+            timer: 0,
+            sample_address: 0xc000,
+            sample_length: 1,
+            current_address: 0xc000,
+            bytes_remaining: 0,
+            buffer: None,
+            shift: 0,
+            bits_remaining: 8,
+            silence: true,
+            level: 0,
+        }
+    }
+
+    // `register` is the address's offset from $4010.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                self.looping = value & 0x40 != 0;
+                self.period = RATE_TABLE[(value & 0x0f) as usize];
+
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            },
+
+            1 => {
+                self.level = value & 0x7f;
+            },
+
+            2 => {
+                self.sample_address = 0xc000 | ((value as u16) << 6);
+            },
+
+            _ => {
+                self.sample_length = ((value as u16) << 4) | 0x01;
+            },
+        }
+    }
+
+    // Bit 4 of a $4015 write, which also acknowledges the interrupt.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // The address the memory reader wants a byte from, if its buffer is
+    // empty and the sample has not finished.
+    pub fn fetch_address(&self) -> Option<u16> {
+        if self.buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn fill(&mut self, value: u8) {
+        self.buffer = Some(value);
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period - 1;
+
+        if !self.silence {
+            if self.shift & 0x01 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+
+        self.shift >>= 1;
+        self.bits_remaining -= 1;
+
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+
+            match self.buffer.take() {
+                Some(value) => {
+                    self.shift = value;
+                    self.silence = false;
+                },
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.level
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq);
+        state.write_bool(self.looping);
+        state.write_u16(self.period);
+        state.write_u16(self.timer);
+        state.write_u16(self.sample_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.current_address);
+        state.write_u16(self.bytes_remaining);
+        state.write_bool(self.buffer.is_some());
+        state.write_u8(self.buffer.unwrap_or(0));
+        state.write_u8(self.shift);
+        state.write_u8(self.bits_remaining);
+        state.write_bool(self.silence);
+        state.write_u8(self.level);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.irq_enabled = state.read_bool()?;
+        self.irq = state.read_bool()?;
+        self.looping = state.read_bool()?;
+        self.period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.sample_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        let buffered = state.read_bool()?;
+        let buffer = state.read_u8()?;
+        self.buffer = if buffered { Some(buffer) } else { None };
+        self.shift = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        self.silence = state.read_bool()?;
+        self.level = state.read_u8()? & 0x7f;
+
+        if !RATE_TABLE.contains(&self.period) || self.timer >= self.period
+            || self.current_address < 0x8000 || self.bits_remaining == 0 || self.bits_remaining > 8 {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
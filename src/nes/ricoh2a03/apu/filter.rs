@@ -0,0 +1,95 @@
+use std::f32::consts::PI;
+
+// The analogue filters between the 2A03 and the audio output, which differ
+// between the Famicom and the front-loading NES.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioProfile {
+    // High-pass at 37 Hz and low-pass at 14 kHz.
+    Famicom,
+    // High-pass at 90 Hz and 440 Hz and low-pass at 14 kHz.
+    Nes,
+}
+
+impl AudioProfile {
+    fn filters(self) -> &'static [(FilterKind, f32)] {
+        match self {
+            AudioProfile::Famicom => &[
+                (FilterKind::HighPass, 37.0),
+                (FilterKind::LowPass, 14000.0),
+            ],
+
+            AudioProfile::Nes => &[
+                (FilterKind::HighPass, 90.0),
+                (FilterKind::HighPass, 440.0),
+                (FilterKind::LowPass, 14000.0),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum FilterKind {
+    HighPass,
+    LowPass,
+}
+
+// A first-order RC filter.
+struct Filter {
+    kind: FilterKind,
+    alpha: f32,
+    input: f32,
+    output: f32,
+}
+
+impl Filter {
+    fn new(kind: FilterKind, cutoff: f32, sample_rate: f32) -> Filter {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+
+        let alpha = match kind {
+            FilterKind::HighPass => rc / (rc + dt),
+            FilterKind::LowPass => dt / (rc + dt),
+        };
+
+        Filter {
+            kind,
+            alpha,
+            input: 0.0,
+            output: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.output = match self.kind {
+            FilterKind::HighPass => self.alpha * (self.output + input - self.input),
+            FilterKind::LowPass => self.output + self.alpha * (input - self.output),
+        };
+
+        self.input = input;
+        self.output
+    }
+}
+
+pub struct FilterChain {
+    filters: Vec<Filter>,
+}
+
+impl FilterChain {
+    pub fn new(profile: AudioProfile, sample_rate: f32) -> FilterChain {
+        let filters = profile.filters().iter()
+            .map(|&(kind, cutoff)| Filter::new(kind, cutoff, sample_rate))
+            .collect();
+
+        FilterChain {
+            filters,
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            for filter in self.filters.iter_mut() {
+                *sample = filter.process(*sample);
+            }
+        }
+    }
+}
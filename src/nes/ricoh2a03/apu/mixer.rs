@@ -0,0 +1,123 @@
+// The sound sources that reach the mixer, including whatever the cartridge
+// adds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+    Expansion,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 6] = [
+        Channel::Pulse1,
+        Channel::Pulse2,
+        Channel::Triangle,
+        Channel::Noise,
+        Channel::Dmc,
+        Channel::Expansion,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+            Channel::Expansion => "expansion",
+        }
+    }
+}
+
+// How one channel is mixed. `volume` scales the channel's level going into
+// the DACs and `pan` runs from -1.0 (left) to 1.0 (right).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelSettings {
+    pub enabled: bool,
+    pub volume: f32,
+    pub pan: f32,
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        ChannelSettings {
+            enabled: true,
+            volume: 1.0,
+            pan: 0.0,
+        }
+    }
+}
+
+const LEFT: usize = 0;
+const RIGHT: usize = 1;
+
+// The 2A03's two DACs, using the nonlinear formulas from nesdev. One takes
+// both pulse channels, the other the triangle, noise and DMC, and their
+// outputs add to a level between 0.0 and about 1.0. Channel volumes apply
+// to the DAC inputs, so a muted channel no longer compresses the others.
+pub struct Mixer {
+    settings: [ChannelSettings; 6],
+    // Per side and channel, the volume after muting and panning.
+    gains: [[f32; 6]; 2],
+}
+
+fn dac(input: f32, divisor: f32, scale: f32) -> f32 {
+    if input <= 0.0 {
+        return 0.0;
+    }
+
+    scale / (divisor / input + 100.0)
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Mixer {
+        Mixer {
+            settings: [ChannelSettings::default(); 6],
+            gains: [[1.0; 6]; 2],
+        }
+    }
+
+    pub fn settings(&self, channel: Channel) -> ChannelSettings {
+        self.settings[channel as usize]
+    }
+
+    pub fn set_settings(&mut self, channel: Channel, settings: ChannelSettings) {
+        let settings = ChannelSettings {
+            enabled: settings.enabled,
+            volume: settings.volume.max(0.0),
+            pan: settings.pan.clamp(-1.0, 1.0),
+        };
+
+        let gain = if settings.enabled { settings.volume } else { 0.0 };
+
+        self.settings[channel as usize] = settings;
+        self.gains[LEFT][channel as usize] = gain * (1.0 - settings.pan).min(1.0);
+        self.gains[RIGHT][channel as usize] = gain * (1.0 + settings.pan).min(1.0);
+    }
+
+    // Output is stereo as soon as any channel is panned.
+    pub fn stereo(&self) -> bool {
+        self.settings.iter().any(|settings| settings.pan != 0.0)
+    }
+
+    // `levels` holds each channel's output in `Channel` order: 0-15 for the
+    // pulses, triangle and noise, 0-127 for the DMC, and the expansion audio
+    // already on the mixer's scale.
+    pub fn mix(&self, side: usize, levels: &[f32; 6]) -> f32 {
+        let gains = &self.gains[side];
+
+        let pulse = levels[0] * gains[0] + levels[1] * gains[1];
+        let tnd = 3.0 * levels[2] * gains[2] + 2.0 * levels[3] * gains[3] + levels[4] * gains[4];
+
+        dac(pulse, 8128.0, 95.52) + dac(tnd, 24329.0, 163.67) + levels[5] * gains[5]
+    }
+}
@@ -0,0 +1,462 @@
+mod blip;
+mod dmc;
+pub mod filter;
+pub mod mixer;
+mod noise;
+mod pulse;
+mod triangle;
+mod units;
+
+use nes::ricoh2a03::apu::blip::BlipBuffer;
+use nes::ricoh2a03::apu::dmc::Dmc;
+use nes::ricoh2a03::apu::filter::AudioProfile;
+use nes::ricoh2a03::apu::filter::FilterChain;
+use nes::ricoh2a03::apu::mixer::Channel;
+use nes::ricoh2a03::apu::mixer::ChannelSettings;
+use nes::ricoh2a03::apu::mixer::Mixer;
+use nes::ricoh2a03::apu::noise::Noise;
+use nes::ricoh2a03::apu::pulse::Pulse;
+use nes::ricoh2a03::apu::triangle::Triangle;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+use std::mem;
+
+pub const CPU_CLOCK: f64 = 1_789_773.0;
+
+pub const APU_STATUS: u16 = 0x4015;
+pub const APU_FRAME_COUNTER: u16 = 0x4017;
+
+// Frame sequencer steps, in CPU cycles since the sequence started.
+const QUARTER_1: u32 = 7457;
+const QUARTER_2: u32 = 14913;
+const QUARTER_3: u32 = 22371;
+const FOUR_STEP_IRQ: u32 = 29828;
+const FOUR_STEP_LAST: u32 = 29829;
+const FOUR_STEP_END: u32 = 29830;
+const FIVE_STEP_LAST: u32 = 37281;
+const FIVE_STEP_END: u32 = 37282;
+
+// CPU cycles of audio gathered before they are turned into samples.
+const AUDIO_BLOCK: u32 = 1024;
+
+// Limits on `set_rate_adjust`, which the blip buffer has room for.
+const MAX_RATE_ADJUST: f64 = 0.01;
+
+// One side of the output, or the only one in mono.
+struct AudioOutput {
+    blip: BlipBuffer,
+    filters: FilterChain,
+    level: f32,
+    block: Vec<f32>,
+}
+
+pub struct Apu {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    odd: bool,
+
+    frame_cycle: u32,
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    // A $4017 write restarts the sequence 3 or 4 cycles later.
+    frame_reset_delay: u8,
+
+    // No samples are made until a frontend sets a sample rate.
+    mixer: Mixer,
+    profile: AudioProfile,
+    sample_rate: f64,
+    outputs: Vec<AudioOutput>,
+    expansion: f32,
+    block_cycle: u32,
+    samples: Vec<f32>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        Apu {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            odd: false,
+            frame_cycle: 0,
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_reset_delay: 0,
+            mixer: Mixer::new(),
+            profile: AudioProfile::Nes,
+            sample_rate: 0.0,
+            outputs: Vec::new(),
+            expansion: 0.0,
+            block_cycle: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    // Power on keeps the frontend's audio settings.
+    pub fn power_on(&mut self) {
+        let profile = self.profile;
+        let sample_rate = self.sample_rate;
+        let mixer = mem::take(&mut self.mixer);
+
+        *self = Apu::new();
+        self.profile = profile;
+        self.mixer = mixer;
+        self.set_sample_rate(sample_rate);
+    }
+
+    // Reset silences every channel and restarts the frame sequencer in the
+    // mode it was last set to.
+    pub fn reset(&mut self) {
+        self.write(APU_STATUS, 0x00);
+        self.frame_irq = false;
+        self.frame_cycle = 0;
+        self.frame_reset_delay = 0;
+    }
+
+    pub fn frame_irq(&self) -> bool {
+        self.frame_irq
+    }
+
+    pub fn dmc_irq(&self) -> bool {
+        self.dmc.irq()
+    }
+
+    // A pending DMC sample fetch. The bus halts the CPU to read the byte
+    // and hands it over with `dmc_fill`.
+    pub fn dmc_fetch_address(&self) -> Option<u16> {
+        self.dmc.fetch_address()
+    }
+
+    pub fn dmc_fill(&mut self, value: u8) {
+        self.dmc.fill(value);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.restart_audio();
+    }
+
+    // Makes slightly more or fewer samples per emulated second, so that a
+    // frontend can hold its audio queue steady against clock drift.
+    pub fn set_rate_adjust(&mut self, ratio: f64) {
+        let ratio = ratio.clamp(1.0 - MAX_RATE_ADJUST, 1.0 + MAX_RATE_ADJUST);
+
+        for output in self.outputs.iter_mut() {
+            output.blip.set_rates(CPU_CLOCK, self.sample_rate * ratio);
+        }
+    }
+
+    pub fn set_audio_profile(&mut self, profile: AudioProfile) {
+        self.profile = profile;
+        self.restart_audio();
+    }
+
+    pub fn channel_settings(&self, channel: Channel) -> ChannelSettings {
+        self.mixer.settings(channel)
+    }
+
+    // Panning any channel switches the output to stereo.
+    pub fn set_channel_settings(&mut self, channel: Channel, settings: ChannelSettings) {
+        let stereo = self.mixer.stereo();
+        self.mixer.set_settings(channel, settings);
+
+        if self.mixer.stereo() != stereo {
+            self.restart_audio();
+        }
+    }
+
+    pub fn stereo(&self) -> bool {
+        self.mixer.stereo()
+    }
+
+    // The level of the cartridge's own sound, if it has any, for the
+    // expansion channel.
+    pub fn set_expansion_audio(&mut self, level: f32) {
+        self.expansion = level;
+    }
+
+    fn restart_audio(&mut self) {
+        let sides = match (self.sample_rate > 0.0, self.mixer.stereo()) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+        };
+
+        self.outputs = (0..sides).map(|_| AudioOutput {
+            blip: BlipBuffer::new(CPU_CLOCK, self.sample_rate, AUDIO_BLOCK),
+            filters: FilterChain::new(self.profile, self.sample_rate as f32),
+            level: 0.0,
+            block: Vec::new(),
+        }).collect();
+
+        self.block_cycle = 0;
+        self.samples.clear();
+    }
+
+    // Samples since the last call, with left and right interleaved when the
+    // output is stereo.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        let capacity = self.samples.capacity();
+        mem::replace(&mut self.samples, Vec::with_capacity(capacity))
+    }
+
+    // Reading $4015 acknowledges the frame interrupt but not the DMC's.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+
+        if self.pulse1.length.active() {
+            status |= 0x01;
+        }
+
+        if self.pulse2.length.active() {
+            status |= 0x02;
+        }
+
+        if self.triangle.length.active() {
+            status |= 0x04;
+        }
+
+        if self.noise.length.active() {
+            status |= 0x08;
+        }
+
+        if self.dmc.active() {
+            status |= 0x10;
+        }
+
+        if self.frame_irq {
+            status |= 0x40;
+        }
+
+        if self.dmc.irq() {
+            status |= 0x80;
+        }
+
+        status
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4000..=0x4003 => self.pulse1.write(address - 0x4000, value),
+            0x4004..=0x4007 => self.pulse2.write(address - 0x4004, value),
+            0x4008..=0x400b => self.triangle.write(address - 0x4008, value),
+            0x400c..=0x400f => self.noise.write(address - 0x400c, value),
+            0x4010..=0x4013 => self.dmc.write(address - 0x4010, value),
+
+            APU_STATUS => {
+                self.pulse1.length.set_enabled(value & 0x01 != 0);
+                self.pulse2.length.set_enabled(value & 0x02 != 0);
+                self.triangle.length.set_enabled(value & 0x04 != 0);
+                self.noise.length.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+            },
+
+            APU_FRAME_COUNTER => {
+                self.five_step = value & 0x80 != 0;
+                self.irq_inhibit = value & 0x40 != 0;
+
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+
+                self.frame_reset_delay = if self.odd { 4 } else { 3 };
+            },
+
+            _ => {},
+        }
+    }
+
+    // Advances one CPU cycle.
+    pub fn tick(&mut self) {
+        self.odd = !self.odd;
+
+        if self.odd {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        self.clock_frame_counter();
+
+        if !self.outputs.is_empty() {
+            self.sample();
+        }
+    }
+
+    fn clock_frame_counter(&mut self) {
+        if self.frame_reset_delay > 0 {
+            self.frame_reset_delay -= 1;
+
+            if self.frame_reset_delay == 0 {
+                self.frame_cycle = 0;
+
+                if self.five_step {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+        }
+
+        match (self.frame_cycle, self.five_step) {
+            (QUARTER_1, _) | (QUARTER_3, _) => {
+                self.clock_quarter_frame();
+            },
+
+            (QUARTER_2, _) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            },
+
+            (FOUR_STEP_IRQ, false) => {
+                self.raise_frame_irq();
+            },
+
+            (FOUR_STEP_LAST, false) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                self.raise_frame_irq();
+            },
+
+            (FOUR_STEP_END, false) => {
+                self.raise_frame_irq();
+                self.frame_cycle = 0;
+            },
+
+            (FIVE_STEP_LAST, true) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            },
+
+            (FIVE_STEP_END, true) => {
+                self.frame_cycle = 0;
+            },
+
+            _ => {},
+        }
+
+        self.frame_cycle += 1;
+    }
+
+    fn raise_frame_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.frame_irq = true;
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_half_frame();
+        self.pulse2.clock_half_frame();
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+
+    fn sample(&mut self) {
+        let levels = [
+            self.pulse1.output() as f32,
+            self.pulse2.output() as f32,
+            self.triangle.output() as f32,
+            self.noise.output() as f32,
+            self.dmc.output() as f32,
+            self.expansion,
+        ];
+
+        for (side, output) in self.outputs.iter_mut().enumerate() {
+            let level = self.mixer.mix(side, &levels);
+
+            if level != output.level {
+                output.blip.add_delta(self.block_cycle, level - output.level);
+                output.level = level;
+            }
+        }
+
+        self.block_cycle += 1;
+
+        if self.block_cycle < AUDIO_BLOCK {
+            return;
+        }
+
+        self.block_cycle = 0;
+
+        for output in self.outputs.iter_mut() {
+            output.block.clear();
+            output.blip.end_frame(AUDIO_BLOCK, &mut output.block);
+            output.filters.process(&mut output.block);
+        }
+
+        match self.outputs.as_slice() {
+            [mono] => self.samples.extend_from_slice(&mono.block),
+            [left, right] => {
+                for (&left, &right) in left.block.iter().zip(right.block.iter()) {
+                    self.samples.push(left);
+                    self.samples.push(right);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_bool(self.odd);
+        state.write_u32(self.frame_cycle);
+        state.write_bool(self.five_step);
+        state.write_bool(self.irq_inhibit);
+        state.write_bool(self.frame_irq);
+        state.write_u8(self.frame_reset_delay);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.odd = state.read_bool()?;
+        self.frame_cycle = state.read_u32()?;
+        self.five_step = state.read_bool()?;
+        self.irq_inhibit = state.read_bool()?;
+        self.frame_irq = state.read_bool()?;
+        self.frame_reset_delay = state.read_u8()?;
+
+        if self.frame_cycle > FIVE_STEP_END || self.frame_reset_delay > 4 {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
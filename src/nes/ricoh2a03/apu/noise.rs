@@ -0,0 +1,110 @@
+use nes::ricoh2a03::apu::units::Envelope;
+use nes::ricoh2a03::apu::units::LengthCounter;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+
+// NTSC timer periods in CPU cycles.
+static PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+pub struct Noise {
+    pub length: LengthCounter,
+    envelope: Envelope,
+
+    // Short mode taps bit 6 instead of bit 1, for a 93 step sequence.
+    short: bool,
+    shift: u16,
+    period: u16,
+    timer: u16,
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            length: LengthCounter::new(),
+            envelope: Envelope::new(),
+            short: false,
+            shift: 1,
+            period: PERIOD_TABLE[0],
+            timer: 0,
+        }
+    }
+
+    // `register` is the address's offset from $400C.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.length.set_halt(value & 0x20 != 0);
+                self.envelope.write(value);
+            },
+
+            1 => {},
+
+            2 => {
+                self.short = value & 0x80 != 0;
+                self.period = PERIOD_TABLE[(value & 0x0f) as usize];
+            },
+
+            _ => {
+                self.length.load(value);
+                self.envelope.restart();
+            },
+        }
+    }
+
+    // Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period - 1;
+
+        let tap = if self.short { 6 } else { 1 };
+        let feedback = (self.shift ^ (self.shift >> tap)) & 0x01;
+        self.shift = (self.shift >> 1) | (feedback << 14);
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.shift & 0x01 != 0 {
+            return 0;
+        }
+
+        self.envelope.output()
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.write_bool(self.short);
+        state.write_u16(self.shift);
+        state.write_u16(self.period);
+        state.write_u16(self.timer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.short = state.read_bool()?;
+        self.shift = state.read_u16()? & 0x7fff;
+        self.period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+
+        if !PERIOD_TABLE.contains(&self.period) || self.timer >= self.period {
+            return Err(StateError::Corrupt);
+        }
+
+        Ok(())
+    }
+}
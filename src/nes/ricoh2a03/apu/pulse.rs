@@ -0,0 +1,169 @@
+use nes::ricoh2a03::apu::units::Envelope;
+use nes::ricoh2a03::apu::units::LengthCounter;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+
+static DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+pub struct Pulse {
+    // Pulse 1 negates its sweep in ones' complement, so it sweeps down one
+    // further than pulse 2 does.
+    ones_complement: bool,
+
+    pub length: LengthCounter,
+    envelope: Envelope,
+
+    duty: u8,
+    step: u8,
+    period: u16,
+    timer: u16,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Pulse {
+        Pulse {
+            ones_complement,
+            length: LengthCounter::new(),
+            envelope: Envelope::new(),
+            duty: 0,
+            step: 0,
+            period: 0,
+            timer: 0,
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    // `register` is the address's offset from $4000 or $4004.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length.set_halt(value & 0x20 != 0);
+                self.envelope.write(value);
+            },
+
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            },
+
+            2 => {
+                self.period = (self.period & 0x0700) | value as u16;
+            },
+
+            _ => {
+                self.period = (self.period & 0x00ff) | ((value as u16 & 0x07) << 8);
+                self.length.load(value);
+                self.envelope.restart();
+                self.step = 0;
+            },
+        }
+    }
+
+    // Clocked every other CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift != 0 && !self.muted() {
+            self.period = self.sweep_target();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.period >> self.sweep_shift;
+
+        if !self.sweep_negate {
+            self.period + change
+        } else if self.ones_complement {
+            self.period.saturating_sub(change + 1)
+        } else {
+            self.period.saturating_sub(change)
+        }
+    }
+
+    // Periods under 8 and sweeps that would overflow silence the channel,
+    // whether or not the sweep is enabled.
+    fn muted(&self) -> bool {
+        self.period < 8 || self.sweep_target() > 0x07ff
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.muted() || DUTY_TABLE[self.duty as usize][self.step as usize] == 0 {
+            return 0;
+        }
+
+        self.envelope.output()
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.write_u8(self.duty);
+        state.write_u8(self.step);
+        state.write_u16(self.period);
+        state.write_u16(self.timer);
+        state.write_bool(self.sweep_enabled);
+        state.write_u8(self.sweep_period);
+        state.write_bool(self.sweep_negate);
+        state.write_u8(self.sweep_shift);
+        state.write_u8(self.sweep_divider);
+        state.write_bool(self.sweep_reload);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.duty = state.read_u8()? & 0x03;
+        self.step = state.read_u8()? & 0x07;
+        self.period = state.read_u16()? & 0x07ff;
+        self.timer = state.read_u16()? & 0x07ff;
+        self.sweep_enabled = state.read_bool()?;
+        self.sweep_period = state.read_u8()? & 0x07;
+        self.sweep_negate = state.read_bool()?;
+        self.sweep_shift = state.read_u8()? & 0x07;
+        self.sweep_divider = state.read_u8()? & 0x07;
+        self.sweep_reload = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use nes::ricoh2a03::apu::units::LengthCounter;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
+
+static SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10,  9,  8,  7,  6,  5,  4,  3,  2,  1,  0,
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+];
+
+pub struct Triangle {
+    pub length: LengthCounter,
+
+    control: bool,
+    linear_period: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+
+    step: u8,
+    period: u16,
+    timer: u16,
+}
+
+impl Triangle {
+    pub fn new() -> Triangle {
+        Triangle {
+            length: LengthCounter::new(),
+            control: false,
+            linear_period: 0,
+            linear_counter: 0,
+            linear_reload: false,
+            step: 0,
+            period: 0,
+            timer: 0,
+        }
+    }
+
+    // `register` is the address's offset from $4008.
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length.set_halt(self.control);
+                self.linear_period = value & 0x7f;
+            },
+
+            1 => {},
+
+            2 => {
+                self.period = (self.period & 0x0700) | value as u16;
+            },
+
+            _ => {
+                self.period = (self.period & 0x00ff) | ((value as u16 & 0x07) << 8);
+                self.length.load(value);
+                self.linear_reload = true;
+            },
+        }
+    }
+
+    // Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period;
+
+        // Periods under 2 are far above hearing and only add a pop when the
+        // output settles somewhere in the middle, so they hold still.
+        if self.length.active() && self.linear_counter > 0 && self.period >= 2 {
+            self.step = (self.step + 1) % 32;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_period;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length.clock();
+    }
+
+    // Silencing the triangle stops the sequencer where it is rather than
+    // dropping the output to zero.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.length.save_state(state);
+        state.write_bool(self.control);
+        state.write_u8(self.linear_period);
+        state.write_u8(self.linear_counter);
+        state.write_bool(self.linear_reload);
+        state.write_u8(self.step);
+        state.write_u16(self.period);
+        state.write_u16(self.timer);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.length.load_state(state)?;
+        self.control = state.read_bool()?;
+        self.linear_period = state.read_u8()? & 0x7f;
+        self.linear_counter = state.read_u8()? & 0x7f;
+        self.linear_reload = state.read_bool()?;
+        self.step = state.read_u8()? & 0x1f;
+        self.period = state.read_u16()? & 0x07ff;
+        self.timer = state.read_u16()? & 0x07ff;
+        Ok(())
+    }
+}
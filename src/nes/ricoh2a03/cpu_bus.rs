@@ -0,0 +1,101 @@
+// Everything the CPU core needs from the machine around it. Every read and
+// write is preceded by exactly one call to `tick`, so an implementation sees
+// the CPU's bus activity cycle by cycle.
+//
+// An implementation may stall the CPU for DMA by advancing its own clock
+// inside `read` or `write`. The CPU doesn't see those cycles: it doesn't
+// poll `irq` or `nmi` during them, and picks up the lines as they are on its
+// next `tick`. A level that is still asserted is therefore only delayed,
+// but an NMI pulse that starts and ends within the stall is lost.
+pub trait CpuBus {
+	fn read(&mut self, address: u16) -> u8;
+	fn write(&mut self, address: u16, value: u8);
+
+	// Advances the rest of the machine by one CPU cycle.
+	fn tick(&mut self);
+
+	// Level of the IRQ line and of the NMI line, both active high. The CPU
+	// does its own edge detection on NMI.
+	fn irq(&self) -> bool;
+	fn nmi(&self) -> bool;
+
+	// Reads without side effects, for disassembly and tracing.
+	fn peek(&self, address: u16) -> u8;
+}
+
+// 64 KiB of RAM and nothing else, for running the core on its own.
+pub struct FlatBus {
+	memory: Box<[u8]>,
+	cycles: u64,
+	irq: bool,
+	nmi: bool,
+}
+
+impl Default for FlatBus {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl FlatBus {
+	pub fn new() -> FlatBus {
+		FlatBus {
+			memory: vec![0; 0x10000].into_boxed_slice(),
+			cycles: 0,
+			irq: false,
+			nmi: false,
+		}
+	}
+
+	pub fn load(&mut self, address: u16, data: &[u8]) {
+		let start = address as usize;
+		let end = (start + data.len()).min(self.memory.len());
+		self.memory[start..end].copy_from_slice(&data[..end - start]);
+	}
+
+	pub fn memory(&self) -> &[u8] {
+		&self.memory
+	}
+
+	pub fn memory_mut(&mut self) -> &mut [u8] {
+		&mut self.memory
+	}
+
+	pub fn cycles(&self) -> u64 {
+		self.cycles
+	}
+
+	pub fn set_irq(&mut self, irq: bool) {
+		self.irq = irq;
+	}
+
+	pub fn set_nmi(&mut self, nmi: bool) {
+		self.nmi = nmi;
+	}
+}
+
+impl CpuBus for FlatBus {
+	fn read(&mut self, address: u16) -> u8 {
+		self.memory[address as usize]
+	}
+
+	fn write(&mut self, address: u16, value: u8) {
+		self.memory[address as usize] = value;
+	}
+
+	fn tick(&mut self) {
+		self.cycles += 1;
+	}
+
+	fn irq(&self) -> bool {
+		self.irq
+	}
+
+	fn nmi(&self) -> bool {
+		self.nmi
+	}
+
+	fn peek(&self, address: u16) -> u8 {
+		self.memory[address as usize]
+	}
+}
@@ -0,0 +1,33 @@
+use nes::ricoh2a03::opcodes::Mode;
+use nes::ricoh2a03::opcodes::OPCODES;
+
+// Formats the instruction at `pc` in the usual 6502 assembler syntax. `bytes`
+// holds the opcode followed by up to two operand bytes; any the instruction
+// does not use are ignored.
+pub fn disassemble(pc: u16, bytes: [u8; 3]) -> String {
+	let opcode = &OPCODES[bytes[0] as usize];
+	let byte = bytes[1];
+	let word = (bytes[1] as u16) | ((bytes[2] as u16) << 8);
+
+	let operand = match opcode.mode {
+		Mode::Implied => return opcode.mnemonic().to_string(),
+		Mode::Accumulator => "A".to_string(),
+		Mode::Immediate => format!("#${:02X}", byte),
+		Mode::ZeroPage => format!("${:02X}", byte),
+		Mode::ZeroPageX => format!("${:02X},X", byte),
+		Mode::ZeroPageY => format!("${:02X},Y", byte),
+		Mode::Absolute => format!("${:04X}", word),
+		Mode::AbsoluteX => format!("${:04X},X", word),
+		Mode::AbsoluteY => format!("${:04X},Y", word),
+		Mode::Indirect => format!("(${:04X})", word),
+		Mode::IndirectX => format!("(${:02X},X)", byte),
+		Mode::IndirectY => format!("(${:02X}),Y", byte),
+		Mode::Relative => format!("${:04X}", branch_target(pc, byte)),
+	};
+
+	format!("{} {}", opcode.mnemonic(), operand)
+}
+
+pub fn branch_target(pc: u16, offset: u8) -> u16 {
+	pc.wrapping_add(2).wrapping_add(offset as i8 as u16)
+}
@@ -1,24 +1,98 @@
-extern crate sdl2;
-
+use nes::ricoh2a03::cpu_bus::CpuBus;
+use nes::ricoh2a03::disassembler;
 use nes::ricoh2a03::Ricoh2A03;
-use sdl2::keyboard::*;
 
-impl Ricoh2A03 {
+impl<B: CpuBus> Ricoh2A03<B> {
+	fn add_binary(&mut self, value: u8) {
+		let a = self.a as u16;
+		let value = value as u16;
+		let result = a + value + self.p.carry as u16;
+
+		self.p.carry = result > 0xff;
+		self.p.overflow = ((a ^ result) & !(a ^ value) & 0x80) != 0;
+		self.a = result as u8;
+		self.set_nz(result as u8);
+	}
+
+	fn decimal(&self) -> bool {
+		self.decimal_mode && self.p.decimal
+	}
+
+	// ADC. In decimal mode the NMOS 6502 sets Z from the binary sum and N and
+	// V from the sum before the high digit is adjusted.
+	pub fn add(&mut self, value: u8) {
+		if !self.decimal() {
+			return self.add_binary(value);
+		}
+
+		let a = self.a as u16;
+		let value = value as u16;
+		let carry = self.p.carry as u16;
+
+		let mut low = (a & 0x0f) + (value & 0x0f) + carry;
+		if low >= 0x0a {
+			low = ((low + 0x06) & 0x0f) + 0x10;
+		}
+
+		let mut result = (a & 0xf0) + (value & 0xf0) + low;
+		self.p.negative = (result & 0x80) != 0;
+		self.p.overflow = ((a ^ result) & !(a ^ value) & 0x80) != 0;
+		self.p.zero = ((a + value + carry) & 0xff) == 0;
+
+		if result >= 0xa0 {
+			result += 0x60;
+		}
+
+		self.p.carry = result > 0xff;
+		self.a = result as u8;
+	}
+
+	// SBC. In decimal mode the NMOS 6502 sets every flag from the binary
+	// difference and only the result is decimal adjusted.
+	pub fn subtract(&mut self, value: u8) {
+		let a = self.a as i16;
+		let borrow = !self.p.carry as i16;
+
+		self.add_binary(value ^ 0xff);
+
+		if !self.decimal() {
+			return;
+		}
+
+		let value = value as i16;
+
+		let mut low = (a & 0x0f) - (value & 0x0f) - borrow;
+		if low < 0 {
+			low = ((low - 0x06) & 0x0f) - 0x10;
+		}
+
+		let mut result = (a & 0xf0) - (value & 0xf0) + low;
+		if result < 0 {
+			result -= 0x60;
+		}
+
+		self.a = result as u8;
+	}
+
 	pub fn cross(&self, a: u16, b: u16) -> bool {
 		(a.wrapping_add(b)) & 0xff00 != a & 0xff00
 	}
 
-    //pub fn draw_nametables(&mut self, texture: &mut sdl2::render::Texture) {
-    //    self.bus.draw_nametables(texture);
-    //}
+    pub fn disassemble(&self, address: u16) -> String {
+        let bytes = [
+            self.bus.peek(address),
+            self.bus.peek(address.wrapping_add(1)),
+            self.bus.peek(address.wrapping_add(2)),
+        ];
 
-    pub fn draw_screen(&self, texture: &mut sdl2::render::Texture) {
-        self.bus.draw_screen(texture);
+        disassembler::disassemble(address, bytes)
     }
 
-    //pub fn draw_tiles(&mut self, texture: &mut sdl2::render::Texture) {
-    //    self.bus.draw_tiles(texture);
-    //}
+	// Pulls spend a cycle reading the stack before the pointer is incremented.
+	pub fn peek_stack(&mut self) {
+		let s = self.s;
+		self.read8(0x100 + (s as u16));
+	}
 
 	pub fn pop8(&mut self) -> u8 {
 		self.s = self.s.wrapping_add(1);
@@ -43,6 +117,12 @@ impl Ricoh2A03 {
 		self.push8(value as u8);
 	}
 
+    // Single-byte instructions still fetch the byte after the opcode.
+    pub fn read_next(&mut self) {
+        let pc = self.pc;
+        self.read8(pc);
+    }
+
     pub fn read8(&mut self, address: u16) -> u8 {
         self.tick();
         self.bus.read(address)
@@ -56,28 +136,32 @@ impl Ricoh2A03 {
 		self.read16_d(address, address + 1)
     }
 
-	pub fn should_redraw(&mut self) -> bool {
-		self.bus.should_redraw()
-	}
-
-    pub fn set_button(&mut self, keycode: Keycode, state: bool) {
-        self.bus.set_button(keycode, state);
-    }
-
 	pub fn set_nz(&mut self, value: u8) {
 		self.p.negative = (value & 0x80) != 0;
 		self.p.zero = value == 0;
 	}
 
 	pub fn should_irq(&mut self) -> bool {
-		false
+		self.irq_pending
 	}
 
 	pub fn should_nmi(&mut self) -> bool {
-		self.bus.should_nmi()
+		self.nmi_pending
 	}
 
+	// The 6502 samples its interrupt lines at the end of each cycle, and
+	// decides whether to take an interrupt from the samples made at the end
+	// of the second-to-last cycle of an instruction. Every access ticks first,
+	// so sampling here, before the cycle runs, sees the state left by the
+	// previous cycle. NMI is edge triggered and stays latched until serviced.
 	pub fn tick(&mut self) {
+		let nmi_line = self.bus.nmi();
+		if nmi_line && !self.nmi_line {
+			self.nmi_pending = true;
+		}
+
+		self.nmi_line = nmi_line;
+		self.irq_pending = self.bus.irq() && !self.p.interrupt;
         self.bus.tick();
 	}
 
@@ -85,4 +169,30 @@ impl Ricoh2A03 {
 		self.tick();
         self.bus.write(address, value)
     }
+}
+
+impl Ricoh2A03 {
+    //pub fn draw_nametables(&mut self, texture: &mut sdl2::render::Texture) {
+    //    self.bus.draw_nametables(texture);
+    //}
+
+    //pub fn draw_tiles(&mut self, texture: &mut sdl2::render::Texture) {
+    //    self.bus.draw_tiles(texture);
+    //}
+
+    pub fn cycles(&self) -> u64 {
+        self.bus.cycles()
+    }
+
+    pub fn framebuffer(&self) -> &[u16] {
+        self.bus.framebuffer()
+    }
+
+	pub fn should_redraw(&mut self) -> bool {
+		self.bus.should_redraw()
+	}
+
+    pub fn set_input(&mut self, port: usize, buttons: u8) {
+        self.bus.set_input(port, buttons);
+    }
 }
\ No newline at end of file
@@ -1,17 +1,37 @@
+use nes::ricoh2a03::cpu_bus::CpuBus;
 use nes::ricoh2a03::Ricoh2A03;
 use nes::ricoh2a03::InterruptType;
+use nes::ricoh2a03::opcodes::Instruction;
+use nes::ricoh2a03::opcodes::Mode;
+use nes::ricoh2a03::opcodes::Opcode;
+use nes::ricoh2a03::opcodes::OPCODES;
 
 pub const NMI_VECTOR: u16 = 0xfffa;
 pub const RESET_VECTOR: u16 = 0xfffc;
 pub const IRQ_VECTOR: u16 = 0xfffe;
 
-pub const TRACE: bool = false;
+// XAA and LAX #imm mix the accumulator with an analog "magic" constant that
+// varies between chips and with temperature; 0xee is the commonly observed one.
+pub const UNSTABLE_MAGIC: u8 = 0xee;
 
-macro_rules! mnemonic {
-	($address: expr, $mnemonic: expr) => {
-		if TRACE {
-			println!("0x{:04x}: {}", $address, $mnemonic);
-		}
+macro_rules! alr {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address) & $cpu.a;
+		$cpu.p.carry = (value & 0x1) != 0;
+		let result = value >> 1;
+		$cpu.a = result;
+		$cpu.set_nz(result);
+	};
+}
+
+macro_rules! anc {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let result = $cpu.read8(address) & $cpu.a;
+		$cpu.a = result;
+		$cpu.set_nz(result);
+		$cpu.p.carry = $cpu.p.negative;
 	};
 }
 
@@ -29,14 +49,20 @@ macro_rules! adc {
 	($cpu: expr, $address: expr) => {
 		let address = $address;
 		let value = $cpu.read8(address);
-		let carry = $cpu.p.carry as u16;
-		let result = ($cpu.a as u16) + (value as u16) + carry;
+		$cpu.add(value);
+	};
+}
 
-		$cpu.p.carry = result > 0xff;
-		$cpu.p.overflow = (($cpu.a ^ result as u8) & !($cpu.a ^ value) & 0x80) != 0;
-		
-		$cpu.a = result as u8;
-		$cpu.set_nz(result as u8);
+macro_rules! arr {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address) & $cpu.a;
+		let carry = $cpu.p.carry as u8;
+		let result = (value >> 1) | (carry << 7);
+		$cpu.a = result;
+		$cpu.set_nz(result);
+		$cpu.p.carry = (result & 0x40) != 0;
+		$cpu.p.overflow = ((result >> 6) ^ (result >> 5)) & 0x1 != 0;
 	};
 }
 
@@ -57,7 +83,19 @@ macro_rules! asl_a {
 		let result = $cpu.a << 1;
 		$cpu.a = result;
 		$cpu.set_nz(result);
-		$cpu.tick();
+		$cpu.read_next();
+	};
+}
+
+macro_rules! axs {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address);
+		let ax = $cpu.a & $cpu.x;
+		let result = ax.wrapping_sub(value);
+		$cpu.p.carry = ax >= value;
+		$cpu.x = result;
+		$cpu.set_nz(result);
 	};
 }
 
@@ -77,11 +115,18 @@ macro_rules! branch {
 		let jump = $cpu.read8(address) as i8 as u16;
 		
 		if $flag == $condition {
-			$cpu.tick();
+			let irq_pending = $cpu.irq_pending;
+			let nmi_pending = $cpu.nmi_pending;
+			$cpu.read_next();
 			let new_pc = $cpu.pc.wrapping_add(jump);
 
 			if ($cpu.pc & 0xff00) != (new_pc & 0xff00) {
-				$cpu.tick();
+				$cpu.read8(($cpu.pc & 0xff00) | (new_pc & 0x00ff));
+			} else {
+				// A taken branch that stays on the same page does not poll
+				// during its last cycle.
+				$cpu.irq_pending &= irq_pending;
+				$cpu.nmi_deferred = !nmi_pending;
 			}
 
 			$cpu.pc = new_pc;
@@ -131,7 +176,7 @@ macro_rules! decr {
 		let reg = $reg.wrapping_sub(1);
 		$reg = reg;
 		$cpu.set_nz(reg);
-		$cpu.tick();
+		$cpu.read_next();
 	};
 }
 
@@ -147,8 +192,8 @@ macro_rules! eor {
 
 macro_rules! flag {
 	($cpu: expr, $flag: expr, $value: expr) => {
+		$cpu.read_next();
 		$flag = $value;
-		$cpu.tick();
 	};
 }
 
@@ -168,7 +213,7 @@ macro_rules! incr {
 		let reg = $reg.wrapping_add(1);
 		$reg = reg;
 		$cpu.set_nz(reg);
-		$cpu.tick();
+		$cpu.read_next();
 	};
 }
 
@@ -181,16 +226,15 @@ macro_rules! isc {
 
 		let result = value.wrapping_add(1);
 		$cpu.write8(address, result);
+		$cpu.subtract(result);
+	};
+}
 
-		let value2 = result ^ 0xff;
-		let carry = $cpu.p.carry as u16;
-		let result2 = ($cpu.a as u16) + (value2 as u16) + carry;
-
-		$cpu.p.carry = result2 > 0xff;
-		$cpu.p.overflow = (($cpu.a ^ result2 as u8) & !($cpu.a ^ value2) & 0x80) != 0;
-		
-		$cpu.a = result2 as u8;
-		$cpu.set_nz(result2 as u8);
+macro_rules! jam {
+	($cpu: expr, $opcode: expr) => {
+		let address = $cpu.pc;
+		$cpu.read8(address);
+		$cpu.halt($opcode);
 	};
 }
 
@@ -211,11 +255,13 @@ macro_rules! jmp_ind {
 
 macro_rules! jsr {
 	($cpu: expr) => {
-		$cpu.tick();
-		let pc = $cpu.pc;
-		$cpu.push16(pc.wrapping_add(1));
 		let address = $cpu.imm();
-		$cpu.pc = $cpu.read16(address);
+		let low = $cpu.read8(address) as u16;
+		$cpu.peek_stack();
+		let pc = $cpu.pc;
+		$cpu.push16(pc);
+		let high = $cpu.read8(pc) as u16;
+		$cpu.pc = low | (high << 8);
 	};
 }
 
@@ -229,6 +275,17 @@ macro_rules! lax {
 	};
 }
 
+macro_rules! las {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address) & $cpu.s;
+		$cpu.a = value;
+		$cpu.x = value;
+		$cpu.s = value;
+		$cpu.set_nz(value);
+	};
+}
+
 macro_rules! ld {
 	($cpu: expr, $address: expr, $reg: expr) => {
 		let address = $address;
@@ -238,6 +295,17 @@ macro_rules! ld {
 	};
 }
 
+macro_rules! lxa {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address);
+		let result = ($cpu.a | UNSTABLE_MAGIC) & value;
+		$cpu.a = result;
+		$cpu.x = result;
+		$cpu.set_nz(result);
+	};
+}
+
 macro_rules! lsr {
 	($cpu: expr, $address: expr) => {
 		let address = $address;
@@ -255,7 +323,7 @@ macro_rules! lsr_a {
 		let result = $cpu.a >> 1;
 		$cpu.a = result;
 		$cpu.set_nz(result);
-		$cpu.tick();
+		$cpu.read_next();
 	};
 }
 
@@ -278,7 +346,7 @@ macro_rules! ora {
 
 macro_rules! pha {
 	($cpu: expr) => {
-		$cpu.tick();
+		$cpu.read_next();
 		let a = $cpu.a;
 		$cpu.push8(a);
 	};
@@ -286,7 +354,7 @@ macro_rules! pha {
 
 macro_rules! php {
 	($cpu: expr) => {
-		$cpu.tick();
+		$cpu.read_next();
 		let status = $cpu.p.read();
 		let brk_flag = 1 << 4;
 		$cpu.push8(status | brk_flag);
@@ -295,8 +363,8 @@ macro_rules! php {
 
 macro_rules! pla {
 	($cpu: expr) => {
-		$cpu.tick();
-		$cpu.tick();
+		$cpu.read_next();
+		$cpu.peek_stack();
 		let a = $cpu.pop8();
 		$cpu.a = a;
 		$cpu.set_nz(a);
@@ -305,8 +373,8 @@ macro_rules! pla {
 
 macro_rules! plp {
 	($cpu: expr) => {
-		$cpu.tick();
-		$cpu.tick();
+		$cpu.read_next();
+		$cpu.peek_stack();
 		let status = $cpu.pop8();
 		$cpu.p.write(status);
 	};
@@ -350,7 +418,7 @@ macro_rules! rol_a {
 		let result = ($cpu.a << 1) | carry;
 		$cpu.a = result;
 		$cpu.set_nz(result);
-		$cpu.tick();
+		$cpu.read_next();
 	};
 }
 
@@ -374,7 +442,7 @@ macro_rules! ror_a {
 		let result = ($cpu.a >> 1) | (carry << 7);
 		$cpu.a = result;
 		$cpu.set_nz(result);
-		$cpu.tick();
+		$cpu.read_next();
 	};
 }
 
@@ -389,15 +457,7 @@ macro_rules! rra {
 		let result = (value >> 1) | (carry << 7);
 		$cpu.write8(address, value);
 		$cpu.write8(address, result);
-
-		let carry = $cpu.p.carry as u16;
-		let result2 = ($cpu.a as u16) + (result as u16) + carry;
-
-		$cpu.p.carry = result2 > 0xff;
-		$cpu.p.overflow = (($cpu.a ^ result2 as u8) & !($cpu.a ^ result) & 0x80) != 0;
-		
-		$cpu.a = result2 as u8;
-		$cpu.set_nz(result2 as u8);
+		$cpu.add(result);
 	};
 }
 
@@ -405,7 +465,7 @@ macro_rules! rti {
 	($cpu: expr) => {
 		let imm = $cpu.imm();
 		$cpu.read8(imm);
-		$cpu.tick();
+		$cpu.peek_stack();
 		let status = $cpu.pop8();
 		$cpu.p.write(status);
 		$cpu.pc = $cpu.pop16();
@@ -416,9 +476,10 @@ macro_rules! rts {
 	($cpu: expr) => {
 		let imm = $cpu.imm();
 		$cpu.read8(imm);
-		$cpu.tick();
-		$cpu.pc = $cpu.pop16().wrapping_add(1);
-		$cpu.tick();
+		$cpu.peek_stack();
+		let address = $cpu.pop16();
+		$cpu.read8(address);
+		$cpu.pc = address.wrapping_add(1);
 	};
 }
 
@@ -433,15 +494,25 @@ macro_rules! sax {
 macro_rules! sbc {
 	($cpu: expr, $address: expr) => {
 		let address = $address;
-		let value = $cpu.read8(address) ^ 0xff;
-		let carry = $cpu.p.carry as u16;
-		let result = ($cpu.a as u16) + (value as u16) + carry;
+		let value = $cpu.read8(address);
+		$cpu.subtract(value);
+	};
+}
 
-		$cpu.p.carry = result > 0xff;
-		$cpu.p.overflow = (($cpu.a ^ result as u8) & !($cpu.a ^ value) & 0x80) != 0;
-		
-		$cpu.a = result as u8;
-		$cpu.set_nz(result as u8);
+macro_rules! sh {
+	($cpu: expr, $base: expr, $index: expr, $value: expr) => {
+		let base = $base;
+		let index = $index as u16;
+		let address = base.wrapping_add(index);
+		$cpu.read8((base & 0xff00) | (address & 0x00ff));
+
+		let value = $value & ((base >> 8) as u8).wrapping_add(1);
+
+		if $cpu.cross(base, index) {
+			$cpu.write8(((value as u16) << 8) | (address & 0x00ff), value);
+		} else {
+			$cpu.write8(address, value);
+		}
 	};
 }
 
@@ -487,54 +558,41 @@ macro_rules! st {
 	};
 }
 
-macro_rules! stabx {
-	($cpu: expr, $reg: expr) => {
-		let abs = $cpu.abs();
-		let address = abs.wrapping_add($cpu.x as u16);
-		let address2 = (abs & 0xff00) + $cpu.x as u16;
-		let value = $reg;
-		$cpu.read8(address2);
-		$cpu.write8(address, value);
-	};
-}
-
-macro_rules! staby {
-	($cpu: expr, $reg: expr) => {
-		let abs = $cpu.abs();
-		let address = abs.wrapping_add($cpu.y as u16);
-		let address2 = (abs & 0xff00) + $cpu.y as u16;
-		let value = $reg;
-		$cpu.read8(address2);
-		$cpu.write8(address, value);
-	};
-}
-
-macro_rules! stindy {
-	($cpu: expr, $reg: expr) => {
-		$cpu.tick();
-		let address = $cpu._indy();
-		let value = $reg;
-		$cpu.write8(address, value);
-	};
-}
-
 macro_rules! tr {
 	($cpu: expr, $src: expr, $dest: expr) => {
 		let src = $src;
 		$dest = src;
 		$cpu.set_nz(src);
-		$cpu.tick();
+		$cpu.read_next();
+	};
+}
+
+macro_rules! tas {
+	($cpu: expr, $base: expr) => {
+		let base = $base;
+		$cpu.s = $cpu.a & $cpu.x;
+		sh!($cpu, base, $cpu.y, $cpu.s);
 	};
 }
 
 macro_rules! trxs {
 	($cpu: expr) => {
 		$cpu.s = $cpu.x;
-		$cpu.tick();
+		$cpu.read_next();
+	};
+}
+
+macro_rules! xaa {
+	($cpu: expr, $address: expr) => {
+		let address = $address;
+		let value = $cpu.read8(address);
+		let result = ($cpu.a | UNSTABLE_MAGIC) & $cpu.x & value;
+		$cpu.a = result;
+		$cpu.set_nz(result);
 	};
 }
 
-impl Ricoh2A03 {
+impl<B: CpuBus> Ricoh2A03<B> {
 	pub fn interrupt(&mut self, t: InterruptType) {
 		if t != InterruptType::BRK {
 			let addr = self.pc;
@@ -552,261 +610,141 @@ impl Ricoh2A03 {
 			let status = self.p.read();
 			self.push8(status | brk_bit);
 		} else {
-			self.s = self.s.wrapping_sub(3);
-			self.tick();
-			self.tick();
-			self.tick();
+			// Reset goes through the pushes with writes suppressed.
+			for _ in 0..3 {
+				self.peek_stack();
+				self.s = self.s.wrapping_sub(1);
+			}
 		}
 
 		self.p.interrupt = true;
-		
+
+		// An NMI edge seen by the time the status byte has been pushed takes
+		// over the vector fetch of a BRK or IRQ, which then never runs.
 		let vector = match t {
-			InterruptType::NMI => NMI_VECTOR,
 			InterruptType::RESET => RESET_VECTOR,
-			InterruptType::IRQ => IRQ_VECTOR,
-			InterruptType::BRK => IRQ_VECTOR,
+			_ if self.nmi_pending => {
+				self.nmi_pending = false;
+				NMI_VECTOR
+			},
+			_ => IRQ_VECTOR,
 		};
-		
+
 		self.pc = self.read16(vector);
+
+		// Nothing is polled during the sequence, so the handler's first
+		// instruction always runs before a later NMI is taken.
+		self.nmi_deferred = true;
 	}
 
 	pub fn step(&mut self) {
+		if self.halted() {
+			self.tick();
+			return;
+		}
+
+		let nmi_deferred = self.nmi_deferred;
+		self.nmi_deferred = false;
+
+		if self.should_nmi() && !nmi_deferred {
+			self.interrupt(InterruptType::NMI);
+			return;
+		}
+
+		if self.should_irq() {
+			self.interrupt(InterruptType::IRQ);
+			return;
+		}
+
 		let pc = self.imm();
-        let opcode = self.read8(pc);
-
-        match opcode {
-			0x00 => { mnemonic!(pc, "BRK"); self.interrupt(InterruptType::BRK); },
-			0x01 => { mnemonic!(pc, "ORA ind,x"); ora!(self, self.indx()); },
-			0x03 => { mnemonic!(pc, "SLO ind,x"); slo!(self, self.indx()); },
-			0x04 => { mnemonic!(pc, "NOP"); nop!(self, self.zp()); },
-            0x05 => { mnemonic!(pc, "ORA zp"); ora!(self, self.zp()); },
-			0x06 => { mnemonic!(pc, "ASL zp"); asl!(self, self.zp()); },
-			0x07 => { mnemonic!(pc, "SLO zp"); slo!(self, self.zp()); },
-			0x08 => { mnemonic!(pc, "PHP"); php!(self); },
-			0x09 => { mnemonic!(pc, "ORA #"); ora!(self, self.imm()); },
-			0x0a => { mnemonic!(pc, "ASL A"); asl_a!(self); },
-			0x0c => { mnemonic!(pc, "NOP"); nop!(self, self.abs()); },
-			0x0d => { mnemonic!(pc, "ORA abs"); ora!(self, self.abs()); },
-			0x0e => { mnemonic!(pc, "ASL abs"); asl!(self, self.abs()); },
-			0x0f => { mnemonic!(pc, "SLO abs"); slo!(self, self.abs()); },
-            0x10 => { mnemonic!(pc, "BPL"); branch!(self, self.p.negative, false); },
-			0x11 => { mnemonic!(pc, "ORA ind,y"); ora!(self, self.indy()); },
-			0x13 => { mnemonic!(pc, "SLO ind,y"); slo!(self, self.indy()); },
-			0x14 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0x15 => { mnemonic!(pc, "ORA zp,x"); ora!(self, self.zpx()); },
-			0x16 => { mnemonic!(pc, "ASL zp,x"); asl!(self, self.zpx()); },
-			0x17 => { mnemonic!(pc, "SLO zp,x"); slo!(self, self.zpx()); },
-            0x18 => { mnemonic!(pc, "CLC"); flag!(self, self.p.carry, false); },
-            0x19 => { mnemonic!(pc, "ORA abs,y"); ora!(self, self.absy()); },
-			0x1a => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0x1b => { mnemonic!(pc, "SLO abs,x"); slo!(self, self.absy()); },
-			0x1c => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0x1d => { mnemonic!(pc, "ORA abs,x"); ora!(self, self.absx()); },
-			0x1e => { mnemonic!(pc, "ASL abs,x"); asl!(self, self._absx()); },
-			0x1f => { mnemonic!(pc, "SLO abs,x"); slo!(self, self.absx()); },
-            0x20 => { mnemonic!(pc, "JSR"); jsr!(self); },
-            0x21 => { mnemonic!(pc, "AND ind,x"); and!(self, self.indx()); },
-			0x23 => { mnemonic!(pc, "RLA ind,x"); rla!(self, self.indx()); },
-            0x24 => { mnemonic!(pc, "BIT zp"); bit!(self, self.zp()); },
-            0x25 => { mnemonic!(pc, "AND zp"); and!(self, self.zp()); },
-			0x26 => { mnemonic!(pc, "ROL zp"); rol!(self, self.zp()); },
-			0x27 => { mnemonic!(pc, "RLA zp"); rla!(self, self.zp()); },
-			0x28 => { mnemonic!(pc, "PLP"); plp!(self); },
-			0x29 => { mnemonic!(pc, "AND #"); and!(self, self.imm()); },
-			0x2a => { mnemonic!(pc, "ROL A"); rol_a!(self); },
-			0x2c => { mnemonic!(pc, "BIT abs"); bit!(self, self.abs()); },
-			0x2d => { mnemonic!(pc, "AND abs"); and!(self, self.abs()); },
-			0x2e => { mnemonic!(pc, "ROL abs"); rol!(self, self.abs()); },
-			0x2f => { mnemonic!(pc, "RLA abs"); rla!(self, self.abs()); },
-			0x30 => { mnemonic!(pc, "BMI"); branch!(self, self.p.negative, true); },
-			0x31 => { mnemonic!(pc, "AND ind,y"); and!(self, self.indy()); },
-			0x33 => { mnemonic!(pc, "RLA ind,y"); rla!(self, self.indy()); },
-			0x34 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0x35 => { mnemonic!(pc, "AND zp,x"); and!(self, self.zpx()); },
-			0x36 => { mnemonic!(pc, "ROL zp,x"); rol!(self, self.zpx()); },
-			0x37 => { mnemonic!(pc, "RLA zp,x"); rla!(self, self.zpx()); },
-            0x38 => { mnemonic!(pc, "SEC"); flag!(self, self.p.carry, true); },
-            0x39 => { mnemonic!(pc, "AND abs,y"); and!(self, self.absy()); },
-			0x3a => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0x3b => { mnemonic!(pc, "RLA abs,x"); rla!(self, self.absy()); },
-			0x3c => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0x3d => { mnemonic!(pc, "AND abs,x"); and!(self, self.absx()); },
-			0x3e => { mnemonic!(pc, "ROL abs,x"); rol!(self, self._absx()); },
-			0x3f => { mnemonic!(pc, "RLA abs,x"); rla!(self, self.absx()); },
-			0x40 => { mnemonic!(pc, "RTI"); rti!(self); },
-			0x41 => { mnemonic!(pc, "EOR ind,x"); eor!(self, self.indx()); },
-			0x43 => { mnemonic!(pc, "SRE ind,x"); sre!(self, self.indx()); },
-			0x44 => { mnemonic!(pc, "NOP"); nop!(self, self.zp()); },
-            0x45 => { mnemonic!(pc, "EOR zp"); eor!(self, self.zp()); },
-			0x46 => { mnemonic!(pc, "LSR zp"); lsr!(self, self.zp()); },
-			0x47 => { mnemonic!(pc, "SRE zp"); sre!(self, self.zp()); },
-			0x48 => { mnemonic!(pc, "PHA"); pha!(self); },
-			0x49 => { mnemonic!(pc, "EOR #"); eor!(self, self.imm()); },
-			0x4a => { mnemonic!(pc, "LSR A"); lsr_a!(self); },
-            0x4c => { mnemonic!(pc, "JMP abs"); jmp!(self); },
-			0x4d => { mnemonic!(pc, "EOR abs"); eor!(self, self.abs()); },
-			0x4e => { mnemonic!(pc, "LSR abs"); lsr!(self, self.abs()); },
-			0x4f => { mnemonic!(pc, "SRE abs"); sre!(self, self.abs()); },
-            0x50 => { mnemonic!(pc, "BVC"); branch!(self, self.p.overflow, false); },
-			0x51 => { mnemonic!(pc, "EOR ind,y"); eor!(self, self.indy()); },
-			0x53 => { mnemonic!(pc, "SRE ind,y"); sre!(self, self.indy()); },
-			0x54 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0x55 => { mnemonic!(pc, "EOR zp,x"); eor!(self, self.zpx()); },
-			0x56 => { mnemonic!(pc, "LSR zp,x"); lsr!(self, self.zpx()); },
-			0x57 => { mnemonic!(pc, "SRE zp,x"); sre!(self, self.zpx()); },
-			0x58 => { mnemonic!(pc, "CLI"); flag!(self, self.p.interrupt, false); },
-            0x59 => { mnemonic!(pc, "EOR abs,y"); eor!(self, self.absy()); },
-			0x5a => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0x5b => { mnemonic!(pc, "SRE abs,x"); sre!(self, self.absy()); },
-			0x5c => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0x5d => { mnemonic!(pc, "EOR abs,x"); eor!(self, self.absx()); },
-			0x5e => { mnemonic!(pc, "LSR abs,x"); lsr!(self, self._absx()); },
-			0x5f => { mnemonic!(pc, "SRE abs,x"); sre!(self, self.absx()); },
-            0x60 => { mnemonic!(pc, "RTS"); rts!(self); },
-			0x61 => { mnemonic!(pc, "ADC ind,x"); adc!(self, self.indx()); },
-			0x63 => { mnemonic!(pc, "RRA ind,x"); rra!(self, self.indx()); },
-			0x64 => { mnemonic!(pc, "NOP"); nop!(self, self.zp()); },
-			0x65 => { mnemonic!(pc, "ADC zp"); adc!(self, self.zp()); },
-			0x66 => { mnemonic!(pc, "ROR zp"); ror!(self, self.zp()); },
-			0x67 => { mnemonic!(pc, "RRA zp"); rra!(self, self.zp()); },
-			0x68 => { mnemonic!(pc, "PLA"); pla!(self); }
-			0x69 => { mnemonic!(pc, "ADC #"); adc!(self, self.imm()); },
-			0x6a => { mnemonic!(pc, "ROR A"); ror_a!(self); },
-			0x6c => { mnemonic!(pc, "JMP ind"); jmp_ind!(self); },
-			0x6d => { mnemonic!(pc, "ADC abs"); adc!(self, self.abs()); },
-			0x6e => { mnemonic!(pc, "ROR abs"); ror!(self, self.abs()); },
-			0x6f => { mnemonic!(pc, "RRA abs"); rra!(self, self.abs()); },
-			0x70 => { mnemonic!(pc, "BVS"); branch!(self, self.p.overflow, true); },
-			0x71 => { mnemonic!(pc, "ADC ind,y"); adc!(self, self.indy()); },
-			0x73 => { mnemonic!(pc, "RRA ind,y"); rra!(self, self.indy()); },
-			0x74 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0x75 => { mnemonic!(pc, "ADC zp,x"); adc!(self, self.zpx()); },
-			0x76 => { mnemonic!(pc, "ROR zp,x"); ror!(self, self.zpx()); },
-			0x77 => { mnemonic!(pc, "RRA zp,x"); rra!(self, self.zpx()); },
-			0x78 => { mnemonic!(pc, "SEI"); flag!(self, self.p.interrupt, true); },
-			0x79 => { mnemonic!(pc, "ADC abs,y"); adc!(self, self.absy()); },
-			0x7a => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0x7b => { mnemonic!(pc, "RRA abs,x"); rra!(self, self.absy()); },
-			0x7c => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0x7d => { mnemonic!(pc, "ADC abs,x"); adc!(self, self.absx()); },
-			0x7e => { mnemonic!(pc, "ROR abs,x"); ror!(self, self._absx()); },
-			0x7f => { mnemonic!(pc, "RRA abs,x"); rra!(self, self.absx()); },
-			0x80 => { mnemonic!(pc, "NOP"); nop!(self, self.imm()); },
-			0x81 => { mnemonic!(pc, "STA ind,x"); st!(self, self.indx(), self.a); },
-			0x82 => { mnemonic!(pc, "NOP"); nop!(self, self.imm()); },
-			0x83 => { mnemonic!(pc, "SAX ind,x"); sax!(self, self.indx()); },
-			0x84 => { mnemonic!(pc, "STY zp"); st!(self, self.zp(), self.y); },
-			0x85 => { mnemonic!(pc, "STA zp"); st!(self, self.zp(), self.a); },
-			0x86 => { mnemonic!(pc, "STX zp"); st!(self, self.zp(), self.x); },
-			0x87 => { mnemonic!(pc, "SAX zp"); sax!(self, self.zp()); },
-			0x88 => { mnemonic!(pc, "DEY"); decr!(self, self.y); },
-			0x89 => { mnemonic!(pc, "NOP"); nop!(self, self.imm()); },
-			0x8a => { mnemonic!(pc, "TXA"); tr!(self, self.x, self.a); },
-			0x8c => { mnemonic!(pc, "STY abs"); st!(self, self.abs(), self.y); },
-			0x8d => { mnemonic!(pc, "STA abs"); st!(self, self.abs(), self.a); },
-			0x8e => { mnemonic!(pc, "STX abs"); st!(self, self.abs(), self.x); },
-			0x8f => { mnemonic!(pc, "SAX abs"); sax!(self, self.abs()); },
-			0x90 => { mnemonic!(pc, "BCC"); branch!(self, self.p.carry, false); },
-			0x91 => { mnemonic!(pc, "STA ind,y"); stindy!(self, self.a); },
-			0x94 => { mnemonic!(pc, "STY zp,x"); st!(self, self.zpx(), self.y); },
-			0x95 => { mnemonic!(pc, "STA zp,x"); st!(self, self.zpx(), self.a);  },
-			0x96 => { mnemonic!(pc, "STX zp,y"); st!(self, self.zpy(), self.x); },
-			0x97 => { mnemonic!(pc, "SAX zp,y"); sax!(self, self.zpy()); },
-			0x98 => { mnemonic!(pc, "TYA"); tr!(self, self.y, self.a); },
-			0x99 => { mnemonic!(pc, "STA abs,y"); staby!(self, self.a); },
-			0x9a => { mnemonic!(pc, "TXS"); trxs!(self); },
-			0x9d => { mnemonic!(pc, "STA abs,x"); stabx!(self, self.a); },
-			0xa0 => { mnemonic!(pc, "LDY #"); ld!(self, self.imm(), self.y); },
-			0xa1 => { mnemonic!(pc, "LDA ind,x"); ld!(self, self.indx(), self.a); },
-			0xa2 => { mnemonic!(pc, "LDX #"); ld!(self, self.imm(), self.x); },
-			0xa3 => { mnemonic!(pc, "LAX ind,x"); lax!(self, self.indx()); },
-			0xa4 => { mnemonic!(pc, "LDY zp"); ld!(self, self.zp(), self.y); },
-			0xa5 => { mnemonic!(pc, "LDA zp"); ld!(self, self.zp(), self.a); },
-			0xa6 => { mnemonic!(pc, "LDX zp"); ld!(self, self.zp(), self.x); },
-			0xa7 => { mnemonic!(pc, "LAX zp"); lax!(self, self.zp()); },
-			0xa8 => { mnemonic!(pc, "TAY"); tr!(self, self.a, self.y); },
-			0xa9 => { mnemonic!(pc, "LDA #"); ld!(self, self.imm(), self.a); },
-			0xaa => { mnemonic!(pc, "TAX"); tr!(self, self.a, self.x); },
-			0xac => { mnemonic!(pc, "LDY abs"); ld!(self, self.abs(), self.y); },
-			0xad => { mnemonic!(pc, "LDA abs"); ld!(self, self.abs(), self.a); },
-			0xae => { mnemonic!(pc, "LDX abs"); ld!(self, self.abs(), self.x); },
-			0xaf => { mnemonic!(pc, "LAX abs"); lax!(self, self.abs()); },
-			0xb0 => { mnemonic!(pc, "BCS"); branch!(self, self.p.carry, true); },
-			0xb1 => { mnemonic!(pc, "LDA ind,y"); ld!(self, self.indy(), self.a); },
-			0xb3 => { mnemonic!(pc, "LAX ind,y"); lax!(self, self.indy()); },
-			0xb4 => { mnemonic!(pc, "LDY zp,x"); ld!(self, self.zpx(), self.y); },
-			0xb5 => { mnemonic!(pc, "LDA zp,x"); ld!(self, self.zpx(), self.a); },
-			0xb6 => { mnemonic!(pc, "LDX zp,y"); ld!(self, self.zpy(), self.x); },
-			0xb7 => { mnemonic!(pc, "LAX zp,y"); lax!(self, self.zpy()); },
-			0xb8 => { mnemonic!(pc, "CLV"); flag!(self, self.p.overflow, false); },
-			0xb9 => { mnemonic!(pc, "LDA abs,y"); ld!(self, self.absy(), self.a); },
-			0xba => { mnemonic!(pc, "TSX"); tr!(self, self.s, self.x); },
-			0xbc => { mnemonic!(pc, "LDY abs,x"); ld!(self, self.absx(), self.y); },
-			0xbd => { mnemonic!(pc, "LDA abs,x"); ld!(self, self.absx(), self.a); },
-			0xbe => { mnemonic!(pc, "LDX abs,y"); ld!(self, self.absy(), self.x); },
-			0xbf => { mnemonic!(pc, "LAX abs,y"); lax!(self, self.absy()); },
-			0xc0 => { mnemonic!(pc, "CPY #"); cmp!(self, self.imm(), self.y); },
-			0xc1 => { mnemonic!(pc, "CMP ind,x"); cmp!(self, self.indx(), self.a); },
-			0xc2 => { mnemonic!(pc, "NOP"); nop!(self, self.imm()); },
-			0xc3 => { mnemonic!(pc, "DCP ind,x"); dcp!(self, self.indx()); }
-			0xc4 => { mnemonic!(pc, "CPY zp"); cmp!(self, self.zp(), self.y); },
-			0xc5 => { mnemonic!(pc, "CMP zp"); cmp!(self, self.zp(), self.a); },
-			0xc6 => { mnemonic!(pc, "DEC zp"); dec!(self, self.zp()); },
-			0xc7 => { mnemonic!(pc, "DCP zp"); dcp!(self, self.zp()); },
-			0xc8 => { mnemonic!(pc, "INY"); incr!(self, self.y); },
-			0xc9 => { mnemonic!(pc, "CMP #"); cmp!(self, self.imm(), self.a); },
-			0xca => { mnemonic!(pc, "DEX"); decr!(self, self.x); },
-			0xcc => { mnemonic!(pc, "CPY abs"); cmp!(self, self.abs(), self.y); },
-			0xcd => { mnemonic!(pc, "CMP abs"); cmp!(self, self.abs(), self.a); },
-			0xce => { mnemonic!(pc, "DEC abs"); dec!(self, self.abs()); },
-			0xcf => { mnemonic!(pc, "DCP abs"); dcp!(self, self.abs()); },
-			0xd0 => { mnemonic!(pc, "BNE"); branch!(self, self.p.zero, false); },
-			0xd1 => { mnemonic!(pc, "CMP ind,y"); cmp!(self, self.indy(), self.a); },
-			0xd3 => { mnemonic!(pc, "DCP ind,y"); dcp!(self, self.indy()); },
-			0xd4 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0xd5 => { mnemonic!(pc, "CMP zp,x"); cmp!(self, self.zpx(), self.a); },
-			0xd6 => { mnemonic!(pc, "DEC zp,x"); dec!(self, self.zpx()); },
-			0xd7 => { mnemonic!(pc, "DCP zp,x"); dcp!(self, self.zpx()); },
-			0xd8 => { mnemonic!(pc, "CLD"); flag!(self, self.p.decimal, false); },
-			0xd9 => { mnemonic!(pc, "CMP abs,y"); cmp!(self, self.absy(), self.a); },
-			0xda => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0xdb => { mnemonic!(pc, "DCP abs,y"); dcp!(self, self.absy()); },
-			0xdc => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0xdd => { mnemonic!(pc, "CMP abs,x"); cmp!(self, self.absx(), self.a); },
-			0xde => { mnemonic!(pc, "DEC abs,x"); dec!(self, self._absx()); },
-			0xdf => { mnemonic!(pc, "DCP abs,x"); dcp!(self, self.absx()); },
-			0xe0 => { mnemonic!(pc, "CPX #"); cmp!(self, self.imm(), self.x); },
-			0xe1 => { mnemonic!(pc, "SBC ind,x"); sbc!(self, self.indx()); },
-			0xe2 => { mnemonic!(pc, "NOP"); nop!(self, self.imm()); },
-			0xe3 => { mnemonic!(pc, "ISC ind,x"); isc!(self, self.indx()); },
-			0xe4 => { mnemonic!(pc, "CPX zp"); cmp!(self, self.zp(), self.x); },
-			0xe5 => { mnemonic!(pc, "SBC zp"); sbc!(self, self.zp()); },
-			0xe6 => { mnemonic!(pc, "INC zp"); inc!(self, self.zp()); },
-			0xe7 => { mnemonic!(pc, "ISC zp"); isc!(self, self.zp()); },
-			0xe8 => { mnemonic!(pc, "INX"); incr!(self, self.x); },
-			0xe9 => { mnemonic!(pc, "SBC #"); sbc!(self, self.imm()); },
-			0xea => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0xeb => { mnemonic!(pc, "SBC #"); sbc!(self, self.imm()); },
-			0xec => { mnemonic!(pc, "CPX abs"); cmp!(self, self.abs(), self.x); },
-			0xed => { mnemonic!(pc, "SBC abs"); sbc!(self, self.abs()); },
-			0xee => { mnemonic!(pc, "INC abs"); inc!(self, self.abs()); },
-			0xef => { mnemonic!(pc, "ISC abs"); isc!(self, self.abs()); },
-			0xf0 => { mnemonic!(pc, "BEQ"); branch!(self, self.p.zero, true); },
-			0xf1 => { mnemonic!(pc, "SBC ind,y"); sbc!(self, self.indy()); },
-			0xf3 => { mnemonic!(pc, "ISC ind,y"); isc!(self, self.indy()); },
-			0xf4 => { mnemonic!(pc, "NOP"); nop!(self, self.zpx()); },
-			0xf5 => { mnemonic!(pc, "SBC zp,x"); sbc!(self, self.zpx()); },
-			0xf6 => { mnemonic!(pc, "INC zp,x"); inc!(self, self.zpx()); },
-			0xf7 => { mnemonic!(pc, "ISC zp,x"); isc!(self, self.zpx()); },
-			0xf8 => { mnemonic!(pc, "SED"); flag!(self, self.p.decimal, true); },
-			0xf9 => { mnemonic!(pc, "SBC abs,y"); sbc!(self, self.absy()); },
-			0xfa => { mnemonic!(pc, "NOP"); nop!(self, pc); },
-			0xfb => { mnemonic!(pc, "ISC abs,y"); isc!(self, self.absy()); },
-			0xfc => { mnemonic!(pc, "NOP"); nop!(self, self.absx()); },
-			0xfd => { mnemonic!(pc, "SBC abs,x"); sbc!(self, self.absx()); },
-			0xfe => { mnemonic!(pc, "INC abs,x"); inc!(self, self._absx()); },
-			0xff => { mnemonic!(pc, "ISC abs,x"); isc!(self, self.absx()); },
-            _ => println!("unimplemented opcode 0x{:02x}", opcode)
-        }
-    }
-}
\ No newline at end of file
+		let opcode = self.read8(pc);
+		self.execute(opcode, &OPCODES[opcode as usize]);
+	}
+
+	fn execute(&mut self, opcode: u8, op: &Opcode) {
+		match op.instruction {
+			Instruction::Adc => { adc!(self, self.address(op)); },
+			Instruction::Ahx => { sh!(self, self.base(op), self.y, self.a & self.x); },
+			Instruction::Alr => { alr!(self, self.address(op)); },
+			Instruction::Anc => { anc!(self, self.address(op)); },
+			Instruction::And => { and!(self, self.address(op)); },
+			Instruction::Arr => { arr!(self, self.address(op)); },
+			Instruction::Asl if op.mode == Mode::Accumulator => { asl_a!(self); },
+			Instruction::Asl => { asl!(self, self.address(op)); },
+			Instruction::Axs => { axs!(self, self.address(op)); },
+			Instruction::Bcc => { branch!(self, self.p.carry, false); },
+			Instruction::Bcs => { branch!(self, self.p.carry, true); },
+			Instruction::Beq => { branch!(self, self.p.zero, true); },
+			Instruction::Bit => { bit!(self, self.address(op)); },
+			Instruction::Bmi => { branch!(self, self.p.negative, true); },
+			Instruction::Bne => { branch!(self, self.p.zero, false); },
+			Instruction::Bpl => { branch!(self, self.p.negative, false); },
+			Instruction::Brk => { self.interrupt(InterruptType::BRK); },
+			Instruction::Bvc => { branch!(self, self.p.overflow, false); },
+			Instruction::Bvs => { branch!(self, self.p.overflow, true); },
+			Instruction::Clc => { flag!(self, self.p.carry, false); },
+			Instruction::Cld => { flag!(self, self.p.decimal, false); },
+			Instruction::Cli => { flag!(self, self.p.interrupt, false); },
+			Instruction::Clv => { flag!(self, self.p.overflow, false); },
+			Instruction::Cmp => { cmp!(self, self.address(op), self.a); },
+			Instruction::Cpx => { cmp!(self, self.address(op), self.x); },
+			Instruction::Cpy => { cmp!(self, self.address(op), self.y); },
+			Instruction::Dcp => { dcp!(self, self.address(op)); },
+			Instruction::Dec => { dec!(self, self.address(op)); },
+			Instruction::Dex => { decr!(self, self.x); },
+			Instruction::Dey => { decr!(self, self.y); },
+			Instruction::Eor => { eor!(self, self.address(op)); },
+			Instruction::Inc => { inc!(self, self.address(op)); },
+			Instruction::Inx => { incr!(self, self.x); },
+			Instruction::Iny => { incr!(self, self.y); },
+			Instruction::Isc => { isc!(self, self.address(op)); },
+			Instruction::Jam => { jam!(self, opcode); },
+			Instruction::Jmp if op.mode == Mode::Indirect => { jmp_ind!(self); },
+			Instruction::Jmp => { jmp!(self); },
+			Instruction::Jsr => { jsr!(self); },
+			Instruction::Las => { las!(self, self.address(op)); },
+			Instruction::Lax => { lax!(self, self.address(op)); },
+			Instruction::Lda => { ld!(self, self.address(op), self.a); },
+			Instruction::Ldx => { ld!(self, self.address(op), self.x); },
+			Instruction::Ldy => { ld!(self, self.address(op), self.y); },
+			Instruction::Lsr if op.mode == Mode::Accumulator => { lsr_a!(self); },
+			Instruction::Lsr => { lsr!(self, self.address(op)); },
+			Instruction::Lxa => { lxa!(self, self.address(op)); },
+			Instruction::Nop if op.mode == Mode::Implied => { nop!(self, self.pc); },
+			Instruction::Nop => { nop!(self, self.address(op)); },
+			Instruction::Ora => { ora!(self, self.address(op)); },
+			Instruction::Pha => { pha!(self); },
+			Instruction::Php => { php!(self); },
+			Instruction::Pla => { pla!(self); },
+			Instruction::Plp => { plp!(self); },
+			Instruction::Rla => { rla!(self, self.address(op)); },
+			Instruction::Rol if op.mode == Mode::Accumulator => { rol_a!(self); },
+			Instruction::Rol => { rol!(self, self.address(op)); },
+			Instruction::Ror if op.mode == Mode::Accumulator => { ror_a!(self); },
+			Instruction::Ror => { ror!(self, self.address(op)); },
+			Instruction::Rra => { rra!(self, self.address(op)); },
+			Instruction::Rti => { rti!(self); },
+			Instruction::Rts => { rts!(self); },
+			Instruction::Sax => { sax!(self, self.address(op)); },
+			Instruction::Sbc => { sbc!(self, self.address(op)); },
+			Instruction::Sec => { flag!(self, self.p.carry, true); },
+			Instruction::Sed => { flag!(self, self.p.decimal, true); },
+			Instruction::Sei => { flag!(self, self.p.interrupt, true); },
+			Instruction::Shx => { sh!(self, self.base(op), self.y, self.x); },
+			Instruction::Shy => { sh!(self, self.base(op), self.x, self.y); },
+			Instruction::Slo => { slo!(self, self.address(op)); },
+			Instruction::Sre => { sre!(self, self.address(op)); },
+			Instruction::Sta => { st!(self, self.address(op), self.a); },
+			Instruction::Stx => { st!(self, self.address(op), self.x); },
+			Instruction::Sty => { st!(self, self.address(op), self.y); },
+			Instruction::Tas => { tas!(self, self.base(op)); },
+			Instruction::Tax => { tr!(self, self.a, self.x); },
+			Instruction::Tay => { tr!(self, self.a, self.y); },
+			Instruction::Tsx => { tr!(self, self.s, self.x); },
+			Instruction::Txa => { tr!(self, self.x, self.a); },
+			Instruction::Txs => { trxs!(self); },
+			Instruction::Tya => { tr!(self, self.y, self.a); },
+			Instruction::Xaa => { xaa!(self, self.address(op)); },
+		}
+	}
+}
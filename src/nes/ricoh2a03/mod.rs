@@ -1,10 +1,19 @@
 mod addressing;
+pub mod apu;
+pub mod cpu_bus;
+pub mod disassembler;
 mod functions;
 mod instructions;
+pub mod opcodes;
 mod status;
+mod trace;
 
 use nes::bus::Bus;
+use nes::ricoh2a03::cpu_bus::CpuBus;
 use nes::ricoh2a03::status::Status;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 
 #[derive(PartialEq)]
 pub enum InterruptType {
@@ -14,18 +23,39 @@ pub enum InterruptType {
 	BRK,
 }
 
-pub struct Ricoh2A03 {
+// The programmer-visible registers, with P packed the way PHP pushes it
+// minus the B flag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    pub p: u8,
+}
+
+pub struct Ricoh2A03<B: CpuBus = Bus> {
     pc: u16,
     a: u8,
     x: u8,
     y: u8,
     s: u8,
     p: Status,
-    bus: Bus,
+    bus: B,
+    jam: Option<u8>,
+    irq_pending: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+    // Set when the last instruction or interrupt sequence didn't poll for
+    // NMI on its last cycle, so an NMI latched then waits one instruction.
+    nmi_deferred: bool,
+    decimal_mode: bool,
 }
 
-impl Ricoh2A03 {
-    pub fn new(bus: Bus) -> Ricoh2A03 {
+impl<B: CpuBus> Ricoh2A03<B> {
+    #[allow(clippy::redundant_field_names)]
+    pub fn new(bus: B) -> Ricoh2A03<B> {
         Ricoh2A03 {
             pc: 0xc000,
             a: 0,
@@ -34,10 +64,133 @@ impl Ricoh2A03 {
             s: 0xfd,
             p: Status::new(),
             bus: bus,
+            jam: None,
+            irq_pending: false,
+            nmi_line: false,
+            nmi_pending: false,
+            nmi_deferred: false,
+            decimal_mode: false,
+        }
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    // The 2A03 keeps the D flag but has no BCD arithmetic. Enabling decimal
+    // mode turns the core into a stock NMOS 6502.
+    pub fn set_decimal_mode(&mut self, enabled: bool) {
+        self.decimal_mode = enabled;
+    }
+
+    pub fn halt(&mut self, opcode: u8) {
+        self.jam = Some(opcode);
+    }
+
+    pub fn halted(&self) -> bool {
+        self.jam.is_some()
+    }
+
+    // Whether the next step runs an instruction rather than an interrupt
+    // sequence or a halted cycle.
+    pub fn at_instruction(&self) -> bool {
+        !self.halted() && (!self.nmi_pending || self.nmi_deferred) && !self.irq_pending
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            pc: self.pc,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            s: self.s,
+            p: self.p.read(),
         }
     }
 
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.pc = registers.pc;
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.s = registers.s;
+        self.p.write(registers.p);
+    }
+
+    pub fn jam_opcode(&self) -> Option<u8> {
+        self.jam
+    }
+
     pub fn reset(&mut self) {
+        self.jam = None;
+        self.irq_pending = false;
+        self.nmi_pending = false;
+        self.nmi_deferred = false;
         self.interrupt(InterruptType::RESET);
     }
-}
\ No newline at end of file
+}
+
+impl Ricoh2A03 {
+
+    pub fn power_on(&mut self) {
+        self.bus.power_on();
+
+        self.a = 0;
+        self.x = 0;
+        self.y = 0;
+        self.s = 0;
+        self.p = Status::new();
+
+        self.reset();
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.pc);
+        state.write_u8(self.a);
+        state.write_u8(self.x);
+        state.write_u8(self.y);
+        state.write_u8(self.s);
+        state.write_u8(self.p.read());
+        state.write_bool(self.jam.is_some());
+        state.write_u8(self.jam.unwrap_or(0));
+        state.write_bool(self.irq_pending);
+        state.write_bool(self.nmi_line);
+        state.write_bool(self.nmi_pending);
+        state.write_bool(self.nmi_deferred);
+
+        self.bus.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.pc = state.read_u16()?;
+        self.a = state.read_u8()?;
+        self.x = state.read_u8()?;
+        self.y = state.read_u8()?;
+        self.s = state.read_u8()?;
+
+        let p = state.read_u8()?;
+        self.p.write(p);
+
+        let halted = state.read_bool()?;
+        let opcode = state.read_u8()?;
+        self.jam = if halted { Some(opcode) } else { None };
+        self.irq_pending = state.read_bool()?;
+        self.nmi_line = state.read_bool()?;
+        self.nmi_pending = state.read_bool()?;
+        self.nmi_deferred = state.read_bool()?;
+
+        self.bus.load_state(state)
+    }
+}
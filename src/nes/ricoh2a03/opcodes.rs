@@ -0,0 +1,495 @@
+use self::Class::*;
+use self::Instruction::*;
+use self::Mode::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+	Adc,
+	Ahx,
+	Alr,
+	Anc,
+	And,
+	Arr,
+	Asl,
+	Axs,
+	Bcc,
+	Bcs,
+	Beq,
+	Bit,
+	Bmi,
+	Bne,
+	Bpl,
+	Brk,
+	Bvc,
+	Bvs,
+	Clc,
+	Cld,
+	Cli,
+	Clv,
+	Cmp,
+	Cpx,
+	Cpy,
+	Dcp,
+	Dec,
+	Dex,
+	Dey,
+	Eor,
+	Inc,
+	Inx,
+	Iny,
+	Isc,
+	Jam,
+	Jmp,
+	Jsr,
+	Las,
+	Lax,
+	Lda,
+	Ldx,
+	Ldy,
+	Lsr,
+	Lxa,
+	Nop,
+	Ora,
+	Pha,
+	Php,
+	Pla,
+	Plp,
+	Rla,
+	Rol,
+	Ror,
+	Rra,
+	Rti,
+	Rts,
+	Sax,
+	Sbc,
+	Sec,
+	Sed,
+	Sei,
+	Shx,
+	Shy,
+	Slo,
+	Sre,
+	Sta,
+	Stx,
+	Sty,
+	Tas,
+	Tax,
+	Tay,
+	Tsx,
+	Txa,
+	Txs,
+	Tya,
+	Xaa,
+}
+
+impl Instruction {
+	pub fn mnemonic(self) -> &'static str {
+		match self {
+			Adc => "ADC",
+			Ahx => "AHX",
+			Alr => "ALR",
+			Anc => "ANC",
+			And => "AND",
+			Arr => "ARR",
+			Asl => "ASL",
+			Axs => "AXS",
+			Bcc => "BCC",
+			Bcs => "BCS",
+			Beq => "BEQ",
+			Bit => "BIT",
+			Bmi => "BMI",
+			Bne => "BNE",
+			Bpl => "BPL",
+			Brk => "BRK",
+			Bvc => "BVC",
+			Bvs => "BVS",
+			Clc => "CLC",
+			Cld => "CLD",
+			Cli => "CLI",
+			Clv => "CLV",
+			Cmp => "CMP",
+			Cpx => "CPX",
+			Cpy => "CPY",
+			Dcp => "DCP",
+			Dec => "DEC",
+			Dex => "DEX",
+			Dey => "DEY",
+			Eor => "EOR",
+			Inc => "INC",
+			Inx => "INX",
+			Iny => "INY",
+			// Spelled the way Nintendulator (and so nestest.log) writes it.
+			Isc => "ISB",
+			Jam => "JAM",
+			Jmp => "JMP",
+			Jsr => "JSR",
+			Las => "LAS",
+			Lax => "LAX",
+			Lda => "LDA",
+			Ldx => "LDX",
+			Ldy => "LDY",
+			Lsr => "LSR",
+			Lxa => "LXA",
+			Nop => "NOP",
+			Ora => "ORA",
+			Pha => "PHA",
+			Php => "PHP",
+			Pla => "PLA",
+			Plp => "PLP",
+			Rla => "RLA",
+			Rol => "ROL",
+			Ror => "ROR",
+			Rra => "RRA",
+			Rti => "RTI",
+			Rts => "RTS",
+			Sax => "SAX",
+			Sbc => "SBC",
+			Sec => "SEC",
+			Sed => "SED",
+			Sei => "SEI",
+			Shx => "SHX",
+			Shy => "SHY",
+			Slo => "SLO",
+			Sre => "SRE",
+			Sta => "STA",
+			Stx => "STX",
+			Sty => "STY",
+			Tas => "TAS",
+			Tax => "TAX",
+			Tay => "TAY",
+			Tsx => "TSX",
+			Txa => "TXA",
+			Txs => "TXS",
+			Tya => "TYA",
+			Xaa => "XAA",
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+	Implied,
+	Accumulator,
+	Immediate,
+	ZeroPage,
+	ZeroPageX,
+	ZeroPageY,
+	Absolute,
+	AbsoluteX,
+	AbsoluteY,
+	Indirect,
+	IndirectX,
+	IndirectY,
+	Relative,
+}
+
+impl Mode {
+	pub const fn length(self) -> u16 {
+		match self {
+			Implied | Accumulator => 1,
+			Immediate | ZeroPage | ZeroPageX | ZeroPageY
+			| IndirectX | IndirectY | Relative => 2,
+			Absolute | AbsoluteX | AbsoluteY | Indirect => 3,
+		}
+	}
+}
+
+// How an instruction uses its effective address. Writes and
+// read-modify-writes always spend the indexing fix-up cycle, reads only when
+// the index carries into the high byte.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+	Read,
+	Write,
+	ReadModifyWrite,
+	Other,
+}
+
+pub struct Opcode {
+	pub instruction: Instruction,
+	pub mode: Mode,
+	pub length: u16,
+	pub cycles: u8,
+	pub page_penalty: bool,
+	pub official: bool,
+	pub class: Class,
+}
+
+impl Opcode {
+	pub fn mnemonic(&self) -> &'static str {
+		self.instruction.mnemonic()
+	}
+}
+
+const fn op(instruction: Instruction, mode: Mode, cycles: u8, class: Class, official: bool) -> Opcode {
+	let indexed = matches!(mode, AbsoluteX | AbsoluteY | IndirectY);
+
+	Opcode {
+		instruction,
+		mode,
+		length: mode.length(),
+		cycles,
+		page_penalty: (indexed && matches!(class, Read)) || matches!(mode, Relative),
+		official,
+		class,
+	}
+}
+
+pub static OPCODES: [Opcode; 256] = [
+	op(Brk, Implied, 7, Other, true), // 0x00
+	op(Ora, IndirectX, 6, Read, true), // 0x01
+	op(Jam, Implied, 2, Other, false), // 0x02
+	op(Slo, IndirectX, 8, ReadModifyWrite, false), // 0x03
+	op(Nop, ZeroPage, 3, Read, false), // 0x04
+	op(Ora, ZeroPage, 3, Read, true), // 0x05
+	op(Asl, ZeroPage, 5, ReadModifyWrite, true), // 0x06
+	op(Slo, ZeroPage, 5, ReadModifyWrite, false), // 0x07
+	op(Php, Implied, 3, Other, true), // 0x08
+	op(Ora, Immediate, 2, Read, true), // 0x09
+	op(Asl, Accumulator, 2, Other, true), // 0x0a
+	op(Anc, Immediate, 2, Read, false), // 0x0b
+	op(Nop, Absolute, 4, Read, false), // 0x0c
+	op(Ora, Absolute, 4, Read, true), // 0x0d
+	op(Asl, Absolute, 6, ReadModifyWrite, true), // 0x0e
+	op(Slo, Absolute, 6, ReadModifyWrite, false), // 0x0f
+	op(Bpl, Relative, 2, Other, true), // 0x10
+	op(Ora, IndirectY, 5, Read, true), // 0x11
+	op(Jam, Implied, 2, Other, false), // 0x12
+	op(Slo, IndirectY, 8, ReadModifyWrite, false), // 0x13
+	op(Nop, ZeroPageX, 4, Read, false), // 0x14
+	op(Ora, ZeroPageX, 4, Read, true), // 0x15
+	op(Asl, ZeroPageX, 6, ReadModifyWrite, true), // 0x16
+	op(Slo, ZeroPageX, 6, ReadModifyWrite, false), // 0x17
+	op(Clc, Implied, 2, Other, true), // 0x18
+	op(Ora, AbsoluteY, 4, Read, true), // 0x19
+	op(Nop, Implied, 2, Other, false), // 0x1a
+	op(Slo, AbsoluteY, 7, ReadModifyWrite, false), // 0x1b
+	op(Nop, AbsoluteX, 4, Read, false), // 0x1c
+	op(Ora, AbsoluteX, 4, Read, true), // 0x1d
+	op(Asl, AbsoluteX, 7, ReadModifyWrite, true), // 0x1e
+	op(Slo, AbsoluteX, 7, ReadModifyWrite, false), // 0x1f
+	op(Jsr, Absolute, 6, Other, true), // 0x20
+	op(And, IndirectX, 6, Read, true), // 0x21
+	op(Jam, Implied, 2, Other, false), // 0x22
+	op(Rla, IndirectX, 8, ReadModifyWrite, false), // 0x23
+	op(Bit, ZeroPage, 3, Read, true), // 0x24
+	op(And, ZeroPage, 3, Read, true), // 0x25
+	op(Rol, ZeroPage, 5, ReadModifyWrite, true), // 0x26
+	op(Rla, ZeroPage, 5, ReadModifyWrite, false), // 0x27
+	op(Plp, Implied, 4, Other, true), // 0x28
+	op(And, Immediate, 2, Read, true), // 0x29
+	op(Rol, Accumulator, 2, Other, true), // 0x2a
+	op(Anc, Immediate, 2, Read, false), // 0x2b
+	op(Bit, Absolute, 4, Read, true), // 0x2c
+	op(And, Absolute, 4, Read, true), // 0x2d
+	op(Rol, Absolute, 6, ReadModifyWrite, true), // 0x2e
+	op(Rla, Absolute, 6, ReadModifyWrite, false), // 0x2f
+	op(Bmi, Relative, 2, Other, true), // 0x30
+	op(And, IndirectY, 5, Read, true), // 0x31
+	op(Jam, Implied, 2, Other, false), // 0x32
+	op(Rla, IndirectY, 8, ReadModifyWrite, false), // 0x33
+	op(Nop, ZeroPageX, 4, Read, false), // 0x34
+	op(And, ZeroPageX, 4, Read, true), // 0x35
+	op(Rol, ZeroPageX, 6, ReadModifyWrite, true), // 0x36
+	op(Rla, ZeroPageX, 6, ReadModifyWrite, false), // 0x37
+	op(Sec, Implied, 2, Other, true), // 0x38
+	op(And, AbsoluteY, 4, Read, true), // 0x39
+	op(Nop, Implied, 2, Other, false), // 0x3a
+	op(Rla, AbsoluteY, 7, ReadModifyWrite, false), // 0x3b
+	op(Nop, AbsoluteX, 4, Read, false), // 0x3c
+	op(And, AbsoluteX, 4, Read, true), // 0x3d
+	op(Rol, AbsoluteX, 7, ReadModifyWrite, true), // 0x3e
+	op(Rla, AbsoluteX, 7, ReadModifyWrite, false), // 0x3f
+	op(Rti, Implied, 6, Other, true), // 0x40
+	op(Eor, IndirectX, 6, Read, true), // 0x41
+	op(Jam, Implied, 2, Other, false), // 0x42
+	op(Sre, IndirectX, 8, ReadModifyWrite, false), // 0x43
+	op(Nop, ZeroPage, 3, Read, false), // 0x44
+	op(Eor, ZeroPage, 3, Read, true), // 0x45
+	op(Lsr, ZeroPage, 5, ReadModifyWrite, true), // 0x46
+	op(Sre, ZeroPage, 5, ReadModifyWrite, false), // 0x47
+	op(Pha, Implied, 3, Other, true), // 0x48
+	op(Eor, Immediate, 2, Read, true), // 0x49
+	op(Lsr, Accumulator, 2, Other, true), // 0x4a
+	op(Alr, Immediate, 2, Read, false), // 0x4b
+	op(Jmp, Absolute, 3, Other, true), // 0x4c
+	op(Eor, Absolute, 4, Read, true), // 0x4d
+	op(Lsr, Absolute, 6, ReadModifyWrite, true), // 0x4e
+	op(Sre, Absolute, 6, ReadModifyWrite, false), // 0x4f
+	op(Bvc, Relative, 2, Other, true), // 0x50
+	op(Eor, IndirectY, 5, Read, true), // 0x51
+	op(Jam, Implied, 2, Other, false), // 0x52
+	op(Sre, IndirectY, 8, ReadModifyWrite, false), // 0x53
+	op(Nop, ZeroPageX, 4, Read, false), // 0x54
+	op(Eor, ZeroPageX, 4, Read, true), // 0x55
+	op(Lsr, ZeroPageX, 6, ReadModifyWrite, true), // 0x56
+	op(Sre, ZeroPageX, 6, ReadModifyWrite, false), // 0x57
+	op(Cli, Implied, 2, Other, true), // 0x58
+	op(Eor, AbsoluteY, 4, Read, true), // 0x59
+	op(Nop, Implied, 2, Other, false), // 0x5a
+	op(Sre, AbsoluteY, 7, ReadModifyWrite, false), // 0x5b
+	op(Nop, AbsoluteX, 4, Read, false), // 0x5c
+	op(Eor, AbsoluteX, 4, Read, true), // 0x5d
+	op(Lsr, AbsoluteX, 7, ReadModifyWrite, true), // 0x5e
+	op(Sre, AbsoluteX, 7, ReadModifyWrite, false), // 0x5f
+	op(Rts, Implied, 6, Other, true), // 0x60
+	op(Adc, IndirectX, 6, Read, true), // 0x61
+	op(Jam, Implied, 2, Other, false), // 0x62
+	op(Rra, IndirectX, 8, ReadModifyWrite, false), // 0x63
+	op(Nop, ZeroPage, 3, Read, false), // 0x64
+	op(Adc, ZeroPage, 3, Read, true), // 0x65
+	op(Ror, ZeroPage, 5, ReadModifyWrite, true), // 0x66
+	op(Rra, ZeroPage, 5, ReadModifyWrite, false), // 0x67
+	op(Pla, Implied, 4, Other, true), // 0x68
+	op(Adc, Immediate, 2, Read, true), // 0x69
+	op(Ror, Accumulator, 2, Other, true), // 0x6a
+	op(Arr, Immediate, 2, Read, false), // 0x6b
+	op(Jmp, Indirect, 5, Other, true), // 0x6c
+	op(Adc, Absolute, 4, Read, true), // 0x6d
+	op(Ror, Absolute, 6, ReadModifyWrite, true), // 0x6e
+	op(Rra, Absolute, 6, ReadModifyWrite, false), // 0x6f
+	op(Bvs, Relative, 2, Other, true), // 0x70
+	op(Adc, IndirectY, 5, Read, true), // 0x71
+	op(Jam, Implied, 2, Other, false), // 0x72
+	op(Rra, IndirectY, 8, ReadModifyWrite, false), // 0x73
+	op(Nop, ZeroPageX, 4, Read, false), // 0x74
+	op(Adc, ZeroPageX, 4, Read, true), // 0x75
+	op(Ror, ZeroPageX, 6, ReadModifyWrite, true), // 0x76
+	op(Rra, ZeroPageX, 6, ReadModifyWrite, false), // 0x77
+	op(Sei, Implied, 2, Other, true), // 0x78
+	op(Adc, AbsoluteY, 4, Read, true), // 0x79
+	op(Nop, Implied, 2, Other, false), // 0x7a
+	op(Rra, AbsoluteY, 7, ReadModifyWrite, false), // 0x7b
+	op(Nop, AbsoluteX, 4, Read, false), // 0x7c
+	op(Adc, AbsoluteX, 4, Read, true), // 0x7d
+	op(Ror, AbsoluteX, 7, ReadModifyWrite, true), // 0x7e
+	op(Rra, AbsoluteX, 7, ReadModifyWrite, false), // 0x7f
+	op(Nop, Immediate, 2, Read, false), // 0x80
+	op(Sta, IndirectX, 6, Write, true), // 0x81
+	op(Nop, Immediate, 2, Read, false), // 0x82
+	op(Sax, IndirectX, 6, Write, false), // 0x83
+	op(Sty, ZeroPage, 3, Write, true), // 0x84
+	op(Sta, ZeroPage, 3, Write, true), // 0x85
+	op(Stx, ZeroPage, 3, Write, true), // 0x86
+	op(Sax, ZeroPage, 3, Write, false), // 0x87
+	op(Dey, Implied, 2, Other, true), // 0x88
+	op(Nop, Immediate, 2, Read, false), // 0x89
+	op(Txa, Implied, 2, Other, true), // 0x8a
+	op(Xaa, Immediate, 2, Read, false), // 0x8b
+	op(Sty, Absolute, 4, Write, true), // 0x8c
+	op(Sta, Absolute, 4, Write, true), // 0x8d
+	op(Stx, Absolute, 4, Write, true), // 0x8e
+	op(Sax, Absolute, 4, Write, false), // 0x8f
+	op(Bcc, Relative, 2, Other, true), // 0x90
+	op(Sta, IndirectY, 6, Write, true), // 0x91
+	op(Jam, Implied, 2, Other, false), // 0x92
+	op(Ahx, IndirectY, 6, Write, false), // 0x93
+	op(Sty, ZeroPageX, 4, Write, true), // 0x94
+	op(Sta, ZeroPageX, 4, Write, true), // 0x95
+	op(Stx, ZeroPageY, 4, Write, true), // 0x96
+	op(Sax, ZeroPageY, 4, Write, false), // 0x97
+	op(Tya, Implied, 2, Other, true), // 0x98
+	op(Sta, AbsoluteY, 5, Write, true), // 0x99
+	op(Txs, Implied, 2, Other, true), // 0x9a
+	op(Tas, AbsoluteY, 5, Write, false), // 0x9b
+	op(Shy, AbsoluteX, 5, Write, false), // 0x9c
+	op(Sta, AbsoluteX, 5, Write, true), // 0x9d
+	op(Shx, AbsoluteY, 5, Write, false), // 0x9e
+	op(Ahx, AbsoluteY, 5, Write, false), // 0x9f
+	op(Ldy, Immediate, 2, Read, true), // 0xa0
+	op(Lda, IndirectX, 6, Read, true), // 0xa1
+	op(Ldx, Immediate, 2, Read, true), // 0xa2
+	op(Lax, IndirectX, 6, Read, false), // 0xa3
+	op(Ldy, ZeroPage, 3, Read, true), // 0xa4
+	op(Lda, ZeroPage, 3, Read, true), // 0xa5
+	op(Ldx, ZeroPage, 3, Read, true), // 0xa6
+	op(Lax, ZeroPage, 3, Read, false), // 0xa7
+	op(Tay, Implied, 2, Other, true), // 0xa8
+	op(Lda, Immediate, 2, Read, true), // 0xa9
+	op(Tax, Implied, 2, Other, true), // 0xaa
+	op(Lxa, Immediate, 2, Read, false), // 0xab
+	op(Ldy, Absolute, 4, Read, true), // 0xac
+	op(Lda, Absolute, 4, Read, true), // 0xad
+	op(Ldx, Absolute, 4, Read, true), // 0xae
+	op(Lax, Absolute, 4, Read, false), // 0xaf
+	op(Bcs, Relative, 2, Other, true), // 0xb0
+	op(Lda, IndirectY, 5, Read, true), // 0xb1
+	op(Jam, Implied, 2, Other, false), // 0xb2
+	op(Lax, IndirectY, 5, Read, false), // 0xb3
+	op(Ldy, ZeroPageX, 4, Read, true), // 0xb4
+	op(Lda, ZeroPageX, 4, Read, true), // 0xb5
+	op(Ldx, ZeroPageY, 4, Read, true), // 0xb6
+	op(Lax, ZeroPageY, 4, Read, false), // 0xb7
+	op(Clv, Implied, 2, Other, true), // 0xb8
+	op(Lda, AbsoluteY, 4, Read, true), // 0xb9
+	op(Tsx, Implied, 2, Other, true), // 0xba
+	op(Las, AbsoluteY, 4, Read, false), // 0xbb
+	op(Ldy, AbsoluteX, 4, Read, true), // 0xbc
+	op(Lda, AbsoluteX, 4, Read, true), // 0xbd
+	op(Ldx, AbsoluteY, 4, Read, true), // 0xbe
+	op(Lax, AbsoluteY, 4, Read, false), // 0xbf
+	op(Cpy, Immediate, 2, Read, true), // 0xc0
+	op(Cmp, IndirectX, 6, Read, true), // 0xc1
+	op(Nop, Immediate, 2, Read, false), // 0xc2
+	op(Dcp, IndirectX, 8, ReadModifyWrite, false), // 0xc3
+	op(Cpy, ZeroPage, 3, Read, true), // 0xc4
+	op(Cmp, ZeroPage, 3, Read, true), // 0xc5
+	op(Dec, ZeroPage, 5, ReadModifyWrite, true), // 0xc6
+	op(Dcp, ZeroPage, 5, ReadModifyWrite, false), // 0xc7
+	op(Iny, Implied, 2, Other, true), // 0xc8
+	op(Cmp, Immediate, 2, Read, true), // 0xc9
+	op(Dex, Implied, 2, Other, true), // 0xca
+	op(Axs, Immediate, 2, Read, false), // 0xcb
+	op(Cpy, Absolute, 4, Read, true), // 0xcc
+	op(Cmp, Absolute, 4, Read, true), // 0xcd
+	op(Dec, Absolute, 6, ReadModifyWrite, true), // 0xce
+	op(Dcp, Absolute, 6, ReadModifyWrite, false), // 0xcf
+	op(Bne, Relative, 2, Other, true), // 0xd0
+	op(Cmp, IndirectY, 5, Read, true), // 0xd1
+	op(Jam, Implied, 2, Other, false), // 0xd2
+	op(Dcp, IndirectY, 8, ReadModifyWrite, false), // 0xd3
+	op(Nop, ZeroPageX, 4, Read, false), // 0xd4
+	op(Cmp, ZeroPageX, 4, Read, true), // 0xd5
+	op(Dec, ZeroPageX, 6, ReadModifyWrite, true), // 0xd6
+	op(Dcp, ZeroPageX, 6, ReadModifyWrite, false), // 0xd7
+	op(Cld, Implied, 2, Other, true), // 0xd8
+	op(Cmp, AbsoluteY, 4, Read, true), // 0xd9
+	op(Nop, Implied, 2, Other, false), // 0xda
+	op(Dcp, AbsoluteY, 7, ReadModifyWrite, false), // 0xdb
+	op(Nop, AbsoluteX, 4, Read, false), // 0xdc
+	op(Cmp, AbsoluteX, 4, Read, true), // 0xdd
+	op(Dec, AbsoluteX, 7, ReadModifyWrite, true), // 0xde
+	op(Dcp, AbsoluteX, 7, ReadModifyWrite, false), // 0xdf
+	op(Cpx, Immediate, 2, Read, true), // 0xe0
+	op(Sbc, IndirectX, 6, Read, true), // 0xe1
+	op(Nop, Immediate, 2, Read, false), // 0xe2
+	op(Isc, IndirectX, 8, ReadModifyWrite, false), // 0xe3
+	op(Cpx, ZeroPage, 3, Read, true), // 0xe4
+	op(Sbc, ZeroPage, 3, Read, true), // 0xe5
+	op(Inc, ZeroPage, 5, ReadModifyWrite, true), // 0xe6
+	op(Isc, ZeroPage, 5, ReadModifyWrite, false), // 0xe7
+	op(Inx, Implied, 2, Other, true), // 0xe8
+	op(Sbc, Immediate, 2, Read, true), // 0xe9
+	op(Nop, Implied, 2, Other, true), // 0xea
+	op(Sbc, Immediate, 2, Read, false), // 0xeb
+	op(Cpx, Absolute, 4, Read, true), // 0xec
+	op(Sbc, Absolute, 4, Read, true), // 0xed
+	op(Inc, Absolute, 6, ReadModifyWrite, true), // 0xee
+	op(Isc, Absolute, 6, ReadModifyWrite, false), // 0xef
+	op(Beq, Relative, 2, Other, true), // 0xf0
+	op(Sbc, IndirectY, 5, Read, true), // 0xf1
+	op(Jam, Implied, 2, Other, false), // 0xf2
+	op(Isc, IndirectY, 8, ReadModifyWrite, false), // 0xf3
+	op(Nop, ZeroPageX, 4, Read, false), // 0xf4
+	op(Sbc, ZeroPageX, 4, Read, true), // 0xf5
+	op(Inc, ZeroPageX, 6, ReadModifyWrite, true), // 0xf6
+	op(Isc, ZeroPageX, 6, ReadModifyWrite, false), // 0xf7
+	op(Sed, Implied, 2, Other, true), // 0xf8
+	op(Sbc, AbsoluteY, 4, Read, true), // 0xf9
+	op(Nop, Implied, 2, Other, false), // 0xfa
+	op(Isc, AbsoluteY, 7, ReadModifyWrite, false), // 0xfb
+	op(Nop, AbsoluteX, 4, Read, false), // 0xfc
+	op(Sbc, AbsoluteX, 4, Read, true), // 0xfd
+	op(Inc, AbsoluteX, 7, ReadModifyWrite, true), // 0xfe
+	op(Isc, AbsoluteX, 7, ReadModifyWrite, false), // 0xff
+];
@@ -19,6 +19,7 @@ impl Status {
         }
     }
 
+    #[allow(clippy::identity_op)]
     pub fn read(&self) -> u8 {
         (self.negative as u8)  << 7  |
 		(self.overflow as u8)  << 6  |
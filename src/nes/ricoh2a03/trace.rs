@@ -0,0 +1,95 @@
+use nes::ricoh2a03::cpu_bus::CpuBus;
+use nes::ricoh2a03::disassembler::disassemble;
+use nes::ricoh2a03::opcodes::Instruction;
+use nes::ricoh2a03::opcodes::Mode;
+use nes::ricoh2a03::opcodes::OPCODES;
+use nes::ricoh2a03::Ricoh2A03;
+use nes::ricoh2c02::PPU_PRERENDER;
+use nes::ricoh2c02::PPU_VBLANK_END;
+
+impl Ricoh2A03 {
+	// Formats the instruction about to run as a line of a Nintendulator
+	// (nestest.log) style trace. Operands are resolved against the current
+	// machine state without side effects.
+	pub fn trace(&self) -> String {
+		let pc = self.pc;
+		let opcode = &OPCODES[self.bus.peek(pc) as usize];
+
+		let mut bytes = [0; 3];
+		for (i, byte) in bytes.iter_mut().enumerate() {
+			*byte = self.bus.peek(pc.wrapping_add(i as u16));
+		}
+
+		let raw = bytes[..opcode.length as usize].iter()
+			.map(|byte| format!("{:02X}", byte))
+			.collect::<Vec<_>>()
+			.join(" ");
+
+		let marker = if opcode.official { ' ' } else { '*' };
+		let text = disassemble(pc, bytes) + &self.trace_operand(bytes);
+
+		// Nintendulator numbers the pre-render line after the vblank lines.
+		let (mut scanline, dot) = self.bus.ppu_position();
+		if scanline == PPU_PRERENDER {
+			scanline = PPU_VBLANK_END + 1;
+		}
+
+		format!("{:04X}  {:<8} {}{:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+			pc, raw, marker, text, self.a, self.x, self.y, self.p.read(), self.s,
+			scanline, dot, self.cycles())
+	}
+
+	fn trace_operand(&self, bytes: [u8; 3]) -> String {
+		let opcode = &OPCODES[bytes[0] as usize];
+		let byte = bytes[1] as u16;
+		let word = byte | ((bytes[2] as u16) << 8);
+
+		match opcode.mode {
+			Mode::ZeroPage => {
+				format!(" = {:02X}", self.bus.peek(byte))
+			},
+
+			Mode::ZeroPageX | Mode::ZeroPageY => {
+				let index = if opcode.mode == Mode::ZeroPageX { self.x } else { self.y };
+				let address = (byte + index as u16) & 0xff;
+				format!(" @ {:02X} = {:02X}", address, self.bus.peek(address))
+			},
+
+			Mode::Absolute => match opcode.instruction {
+				Instruction::Jmp | Instruction::Jsr => String::new(),
+				_ => format!(" = {:02X}", self.bus.peek(word)),
+			},
+
+			Mode::AbsoluteX | Mode::AbsoluteY => {
+				let index = if opcode.mode == Mode::AbsoluteX { self.x } else { self.y };
+				let address = word.wrapping_add(index as u16);
+				format!(" @ {:04X} = {:02X}", address, self.bus.peek(address))
+			},
+
+			Mode::Indirect => {
+				// JMP ($xxFF) fetches the high byte from the start of the page.
+				let high = (word & 0xff00) | (word.wrapping_add(1) & 0xff);
+				let target = self.peek_pointer(word, high);
+				format!(" = {:04X}", target)
+			},
+
+			Mode::IndirectX => {
+				let pointer = (byte + self.x as u16) & 0xff;
+				let address = self.peek_pointer(pointer, (pointer + 1) & 0xff);
+				format!(" @ {:02X} = {:04X} = {:02X}", pointer, address, self.bus.peek(address))
+			},
+
+			Mode::IndirectY => {
+				let base = self.peek_pointer(byte, (byte + 1) & 0xff);
+				let address = base.wrapping_add(self.y as u16);
+				format!(" = {:04X} @ {:04X} = {:02X}", base, address, self.bus.peek(address))
+			},
+
+			_ => String::new(),
+		}
+	}
+
+	fn peek_pointer(&self, low: u16, high: u16) -> u16 {
+		(self.bus.peek(low) as u16) | ((self.bus.peek(high) as u16) << 8)
+	}
+}
@@ -1,7 +1,12 @@
-extern crate sdl2;
+pub mod ntsc;
+pub mod palette;
 
 use nes::mapper::Mapper;
+use nes::ricoh2c02::palette::Palette;
 use nes::rom::MirrorMode;
+use nes::state::StateError;
+use nes::state::StateReader;
+use nes::state::StateWriter;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -24,21 +29,25 @@ pub const PPU_VBLANK_END: isize = 260;
 
 pub const PPU_LAST_CYCLE: usize = 340;
 
-static PALETTE: [u8; 192] = [
-    84, 84, 84,         0, 30, 116,         8, 16, 144,         48, 0, 136,         68, 0, 100,         92, 0, 48,          84, 4, 0,       60, 24, 0,
-    32, 42, 0,          8, 58, 0,           0, 64, 0,           0, 60, 0,           0, 50, 60,          0, 0, 0,            0, 0, 0,        0, 0, 0,
-    152, 150, 152,      8, 76, 196,         48, 50, 236,        92, 30, 228,        136, 20, 176,       160, 20, 100,       152,34, 32,     120, 60, 0,
-    84, 90, 0,          40, 114, 0,         8, 124, 0,          0, 118, 40,         0, 102, 120,        0, 0, 0,            0, 0, 0,        0, 0, 0,
-    236, 238, 236,      76, 154, 236,       120, 124, 236,      176, 98, 236,       228, 84, 236,       236, 88, 180,       236, 106, 100,  212, 136, 32,
-    160, 170, 0,        116, 196, 0,        76, 208, 32,        56, 204, 108,       56, 180, 204,       60, 60, 60,         0, 0, 0,        0, 0, 0,
-    236, 238, 236,      168, 204, 236,      188, 188, 236,      212, 178, 236,      236, 174, 236,      236, 174, 212,      236, 180, 176,  228, 196, 144,
-    204, 210, 120,      180, 222, 120,      168, 226, 144,      152, 226, 180,      160, 214, 228,      160, 162, 160,      0, 0, 0,        0, 0, 0
-];
+// Framebuffer pixels hold a palette index in bits 0-5 and the red, green and
+// blue emphasis bits from PPUMASK in bits 6-8.
+pub fn draw_rgb(framebuffer: &[u16], palette: &Palette, buffer: &mut [u8], pitch: usize) {
+    for y in 0..240 {
+        for x in 0..256 {
+            let buffer_address = (y * pitch) + (x * 3);
+            let framebuffer_address = (y << 8) + x;
+
+            let pixel_colour = framebuffer[framebuffer_address];
+
+            buffer[buffer_address..buffer_address + 3].copy_from_slice(&palette.rgb(pixel_colour));
+        }
+    }
+}
 
 pub struct Ricoh2C02 {
-    mapper: Rc<RefCell<Box<Mapper+Send>>>,
+    mapper: Rc<RefCell<Box<dyn Mapper+Send>>>,
 
-    framebuffer: Box<[u8]>,
+    framebuffer: Box<[u16]>,
 
     nametable_0: Box<[u8]>,
     nametable_1: Box<[u8]>,
@@ -51,6 +60,11 @@ pub struct Ricoh2C02 {
     cycle: usize,
     odd: bool,
 
+    // Colour subcarrier phase at the current dot and at the start of the
+    // frame in the framebuffer, in twelfths of a cycle.
+    phase: u8,
+    frame_phase: u8,
+
     latch: u8,
     read_buffer: u8,
 
@@ -65,12 +79,13 @@ pub struct Ricoh2C02 {
     lc_sprite_enable: bool,
     lc_background_enable: bool,
     greyscale: bool,
+    emphasis: u8,
 
     vblank: bool,
     sprite_0_hit: bool,
     sprite_overflow: bool,
 
-    should_nmi: bool,
+    suppress_vblank: bool,
 
     redraw: bool,
 
@@ -114,7 +129,8 @@ pub struct Ricoh2C02 {
 }
 
 impl Ricoh2C02 {
-    pub fn new(mapper: Rc<RefCell<Box<Mapper+Send>>>) -> Ricoh2C02 {
+    #[allow(clippy::redundant_field_names)]
+    pub fn new(mapper: Rc<RefCell<Box<dyn Mapper+Send>>>) -> Ricoh2C02 {
         Ricoh2C02 {
             mapper: mapper,
 
@@ -131,6 +147,9 @@ impl Ricoh2C02 {
             cycle: 0,
             odd: false,
 
+            phase: 0,
+            frame_phase: 0,
+
             latch: 0,
             read_buffer: 0,
 
@@ -145,12 +164,13 @@ impl Ricoh2C02 {
             lc_sprite_enable: false,
             lc_background_enable: false,
             greyscale: false,
+            emphasis: 0,
 
             vblank: false,
             sprite_0_hit: false,
             sprite_overflow: false,
 
-            should_nmi: false,
+            suppress_vblank: false,
 
             redraw: false,
 
@@ -194,10 +214,29 @@ impl Ricoh2C02 {
         }
     }
 
+    #[allow(clippy::needless_return)]
     pub fn odd(&self) -> bool {
         return self.odd;
     }
 
+    pub fn scanline(&self) -> isize {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn frame_phase(&self) -> u8 {
+        self.frame_phase
+    }
+
+    // Moves the beam without running the dots in between.
+    pub fn set_position(&mut self, scanline: isize, dot: usize) {
+        self.scanline = scanline;
+        self.cycle = dot;
+    }
+
     fn copy_horizontal_bits(&mut self) {
         self.vram_address &= !0x041f;
         self.vram_address |= self.temp_vram_address & 0x041f;
@@ -253,7 +292,8 @@ impl Ricoh2C02 {
     fn draw_pixel(&mut self) {
         let framebuffer_address = ((self.scanline as usize) << 8) + self.cycle - 1;
         let pixel_colour = self.priority_select();
-        self.framebuffer[framebuffer_address] = self.palette_read(pixel_colour);
+        let colour = self.palette_read(pixel_colour) as u16;
+        self.framebuffer[framebuffer_address] = colour | (self.emphasis as u16) << 6;
     }
 
     fn get_pixel_colour(&self) -> u8 {
@@ -406,25 +446,187 @@ impl Ricoh2C02 {
     //    }).unwrap();
     //}
 
-    pub fn draw_screen(&self, texture: &mut sdl2::render::Texture) {
-        texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
-            for y in 0..240 {
-                for x in 0..256 {
-                    let texture_address = (y * pitch) + (x * 3);
-                    let framebuffer_address = (y << 8) + x;
+    pub fn framebuffer(&self) -> &[u16] {
+        &self.framebuffer
+    }
 
-                    let pixel_colour = self.framebuffer[framebuffer_address];
+    pub fn save_state(&self, state: &mut StateWriter) {
+        for &pixel in self.framebuffer.iter() {
+            state.write_u16(pixel);
+        }
 
-                    let palette_address = (pixel_colour * 3) as usize;
+        state.write_bytes(&self.nametable_0);
+        state.write_bytes(&self.nametable_1);
+        state.write_bytes(&self.nametable_2);
+        state.write_bytes(&self.nametable_3);
 
-                    buffer[texture_address] = PALETTE[palette_address];
-                    buffer[texture_address + 1] = PALETTE[palette_address + 1];
-                    buffer[texture_address + 2] = PALETTE[palette_address + 2];
-                }
-            }
-        }).unwrap();
+        state.write_bytes(&self.palette);
+
+        state.write_i64(self.scanline as i64);
+        state.write_u16(self.cycle as u16);
+        state.write_bool(self.odd);
+        state.write_u8(self.phase);
+        state.write_u8(self.frame_phase);
+
+        state.write_u8(self.latch);
+        state.write_u8(self.read_buffer);
+
+        state.write_bool(self.nmi_enable);
+        state.write_u8(self.sprite_size as u8);
+        state.write_u16(self.bg_pattern_table);
+        state.write_u16(self.sprite_pattern_table);
+        state.write_u16(self.vram_increment);
+
+        state.write_bool(self.sprite_enable);
+        state.write_bool(self.background_enable);
+        state.write_bool(self.lc_sprite_enable);
+        state.write_bool(self.lc_background_enable);
+        state.write_bool(self.greyscale);
+        state.write_u8(self.emphasis);
+
+        state.write_bool(self.vblank);
+        state.write_bool(self.sprite_0_hit);
+        state.write_bool(self.sprite_overflow);
+
+        state.write_bool(self.suppress_vblank);
+
+        state.write_bool(self.redraw);
+
+        state.write_u16(self.vram_address);
+        state.write_u16(self.temp_vram_address);
+        state.write_u8(self.fine_x_scroll);
+
+        state.write_bool(self.write_toggle);
+
+        state.write_u16(self.tile_address);
+        state.write_u8(self.tile_low);
+        state.write_u8(self.tile_high);
+
+        state.write_u16(self.tile_shift_low);
+        state.write_u16(self.tile_shift_high);
+
+        state.write_u8(self.attribute_shift_low);
+        state.write_u8(self.attribute_shift_high);
+
+        state.write_u8(self.attribute_latch_low);
+        state.write_u8(self.attribute_latch_high);
+
+        state.write_u8(self.next_attribute_latch_low);
+        state.write_u8(self.next_attribute_latch_high);
+
+        state.write_bytes(&self.oam);
+        state.write_bytes(&self.secondary_oam);
+
+        state.write_u8(self.oam_addr);
+        state.write_u8(self.oam_2_addr);
+        state.write_u8(self.oam_buffer);
+        state.write_bool(self.oam_overflow);
+
+        state.write_u8(self.sprite_fill_count as u8);
+
+        state.write_bytes(&self.sprite_shift_low);
+        state.write_bytes(&self.sprite_shift_high);
+
+        state.write_bytes(&self.sprite_latch);
+        state.write_bytes(&self.sprite_counter);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        for pixel in self.framebuffer.iter_mut() {
+            *pixel = state.read_u16()? & 0x1ff;
+        }
+
+        state.read_bytes(&mut self.nametable_0)?;
+        state.read_bytes(&mut self.nametable_1)?;
+        state.read_bytes(&mut self.nametable_2)?;
+        state.read_bytes(&mut self.nametable_3)?;
+
+        state.read_bytes(&mut self.palette)?;
+
+        self.scanline = state.read_i64()? as isize;
+        self.cycle = state.read_u16()? as usize;
+        self.odd = state.read_bool()?;
+        self.phase = state.read_u8()? % 12;
+        self.frame_phase = state.read_u8()? % 12;
+
+        if self.scanline < PPU_PRERENDER || self.scanline > PPU_VBLANK_END
+        || self.cycle > PPU_LAST_CYCLE {
+            return Err(StateError::Corrupt);
+        }
+
+        self.latch = state.read_u8()?;
+        self.read_buffer = state.read_u8()?;
+
+        self.nmi_enable = state.read_bool()?;
+        self.sprite_size = state.read_u8()? as usize;
+
+        if self.sprite_size != 8 && self.sprite_size != 16 {
+            return Err(StateError::Corrupt);
+        }
+
+        self.bg_pattern_table = state.read_u16()?;
+        self.sprite_pattern_table = state.read_u16()?;
+        self.vram_increment = state.read_u16()?;
+
+        self.sprite_enable = state.read_bool()?;
+        self.background_enable = state.read_bool()?;
+        self.lc_sprite_enable = state.read_bool()?;
+        self.lc_background_enable = state.read_bool()?;
+        self.greyscale = state.read_bool()?;
+        self.emphasis = state.read_u8()? & 0x07;
+
+        self.vblank = state.read_bool()?;
+        self.sprite_0_hit = state.read_bool()?;
+        self.sprite_overflow = state.read_bool()?;
+
+        self.suppress_vblank = state.read_bool()?;
+
+        self.redraw = state.read_bool()?;
+
+        self.vram_address = state.read_u16()?;
+        self.temp_vram_address = state.read_u16()?;
+        self.fine_x_scroll = state.read_u8()?;
+
+        self.write_toggle = state.read_bool()?;
+
+        self.tile_address = state.read_u16()?;
+        self.tile_low = state.read_u8()?;
+        self.tile_high = state.read_u8()?;
+
+        self.tile_shift_low = state.read_u16()?;
+        self.tile_shift_high = state.read_u16()?;
+
+        self.attribute_shift_low = state.read_u8()?;
+        self.attribute_shift_high = state.read_u8()?;
+
+        self.attribute_latch_low = state.read_u8()?;
+        self.attribute_latch_high = state.read_u8()?;
+
+        self.next_attribute_latch_low = state.read_u8()?;
+        self.next_attribute_latch_high = state.read_u8()?;
+
+        state.read_bytes(&mut self.oam)?;
+        state.read_bytes(&mut self.secondary_oam)?;
+
+        self.oam_addr = state.read_u8()?;
+        self.oam_2_addr = state.read_u8()?;
+        self.oam_buffer = state.read_u8()?;
+        self.oam_overflow = state.read_bool()?;
+
+        self.sprite_fill_count = state.read_u8()? as usize;
+
+        if self.sprite_fill_count > 8 {
+            return Err(StateError::Corrupt);
+        }
+
+        state.read_bytes(&mut self.sprite_shift_low)?;
+        state.read_bytes(&mut self.sprite_shift_high)?;
+
+        state.read_bytes(&mut self.sprite_latch)?;
+        state.read_bytes(&mut self.sprite_counter)
     }
 
+    #[allow(clippy::manual_range_contains, clippy::needless_return)]
     pub fn in_range(&self, address: u16) -> bool {
         if (address >= PPU_START) && (address <= PPU_END) {
             return true;
@@ -477,6 +679,12 @@ impl Ricoh2C02 {
             },
 
             PPU_STATUS => {
+                // Reading the status on the dot before vblank starts returns
+                // the flag clear and stops it from being set for this frame.
+                if self.scanline == PPU_VBLANK_START && self.cycle == 1 {
+                    self.suppress_vblank = true;
+                }
+
                 self.latch &= 0x1f;
                 self.latch |= (self.vblank as u8) << 7;
                 self.latch |= (self.sprite_0_hit as u8) << 6;
@@ -576,6 +784,7 @@ impl Ricoh2C02 {
                 self.lc_sprite_enable = (self.latch & 0x04) != 0;
                 self.lc_background_enable = (self.latch & 0x02) != 0;
                 self.greyscale = (self.latch & 0x01) != 0;
+                self.emphasis = self.latch >> 5;
 
                 //println!("0x{:02x} -> PPU_MASK", self.latch);
             },
@@ -647,19 +856,22 @@ impl Ricoh2C02 {
 
     pub fn nametable_read(&mut self, address: u16) -> u8 {
         let address = (address & 0xfff) as usize;
+        let mapper = self.mapper.borrow_mut();
 
         if address < 0x400 {
-            return self.nametable_0[address]
+            match mapper.mirroring() {
+                MirrorMode::OneScreenUpper => return self.nametable_1[address],
+                _ => return self.nametable_0[address],
+            }
         }
 
-        let mapper = self.mapper.borrow_mut();
-
         if address < 0x800 {
             match mapper.mirroring() {
                 MirrorMode::Horizontal => return self.nametable_0[address - 0x400],
                 MirrorMode::Vertical => return self.nametable_1[address - 0x400],
                 MirrorMode::FourScreen => return self.nametable_1[address - 0x400],
                 MirrorMode::OneScreen => return self.nametable_0[address - 0x400],
+                MirrorMode::OneScreenUpper => return self.nametable_1[address - 0x400],
             }
         }
 
@@ -668,7 +880,8 @@ impl Ricoh2C02 {
                 MirrorMode::Horizontal => return self.nametable_2[address - 0x800],
                 MirrorMode::Vertical => return self.nametable_0[address - 0x800],
                 MirrorMode::FourScreen => return self.nametable_2[address - 0x800],
-                MirrorMode::OneScreen => return self.nametable_0[address - 0x800]
+                MirrorMode::OneScreen => return self.nametable_0[address - 0x800],
+                MirrorMode::OneScreenUpper => return self.nametable_1[address - 0x800],
             }
         }
 
@@ -676,25 +889,30 @@ impl Ricoh2C02 {
             MirrorMode::Horizontal => self.nametable_2[address - 0xc00],
             MirrorMode::Vertical => self.nametable_1[address - 0xc00],
             MirrorMode::FourScreen => self.nametable_3[address - 0xc00],
-            MirrorMode::OneScreen => return self.nametable_0[address - 0xc00]
+            MirrorMode::OneScreen => self.nametable_0[address - 0xc00],
+            MirrorMode::OneScreenUpper => self.nametable_1[address - 0xc00],
         }
     }
 
+    #[allow(clippy::needless_return)]
     pub fn nametable_write(&mut self, address: u16, value: u8) {
         let address = (address & 0xfff) as usize;
+        let mapper = self.mapper.borrow_mut();
 
         if address < 0x400 {
-            return self.nametable_0[address] = value;
+            return match mapper.mirroring() {
+                MirrorMode::OneScreenUpper => self.nametable_1[address] = value,
+                _ => self.nametable_0[address] = value,
+            };
         }
 
-        let mapper = self.mapper.borrow_mut();
-
         if address < 0x800 {
             return match mapper.mirroring() {
                 MirrorMode::Horizontal => self.nametable_0[address - 0x400] = value,
                 MirrorMode::Vertical => self.nametable_1[address - 0x400] = value,
                 MirrorMode::FourScreen => self.nametable_1[address - 0x400] = value,
                 MirrorMode::OneScreen => self.nametable_0[address - 0x400] = value,
+                MirrorMode::OneScreenUpper => self.nametable_1[address - 0x400] = value,
             };
         }
 
@@ -703,7 +921,8 @@ impl Ricoh2C02 {
                 MirrorMode::Horizontal => self.nametable_2[address - 0x800] = value,
                 MirrorMode::Vertical => self.nametable_0[address - 0x800] = value,
                 MirrorMode::FourScreen => self.nametable_2[address - 0x800] = value,
-                MirrorMode::OneScreen => self.nametable_2[address - 0x800] = value,
+                MirrorMode::OneScreen => self.nametable_0[address - 0x800] = value,
+                MirrorMode::OneScreenUpper => self.nametable_1[address - 0x800] = value,
             };
         }
 
@@ -711,7 +930,8 @@ impl Ricoh2C02 {
             MirrorMode::Horizontal => self.nametable_2[address - 0xc00] = value,
             MirrorMode::Vertical => self.nametable_1[address - 0xc00] = value,
             MirrorMode::FourScreen => self.nametable_3[address - 0xc00] = value,
-            MirrorMode::OneScreen => self.nametable_3[address - 0xc00] = value,
+            MirrorMode::OneScreen => self.nametable_0[address - 0xc00] = value,
+            MirrorMode::OneScreenUpper => self.nametable_1[address - 0xc00] = value,
         };
     }
 
@@ -748,13 +968,10 @@ impl Ricoh2C02 {
         self.background_enable || self.sprite_enable
     }
 
-    pub fn should_nmi(&mut self) -> bool {
-        if self.should_nmi {
-            self.should_nmi = false;
-            return true;
-        }
-
-        false
+    // Level of the PPU's /NMI output, active high. The CPU does its own edge
+    // detection, so enabling NMI in $2000 during vblank raises another one.
+    pub fn nmi(&self) -> bool {
+        self.vblank && self.nmi_enable
     }
 
     pub fn should_redraw(&mut self) -> bool {
@@ -930,6 +1147,7 @@ impl Ricoh2C02 {
         }
     }
 
+    #[allow(unused_parens, clippy::collapsible_if)]
     pub fn process_scanline(&mut self) {
         if self.cycle <= 256 {
             self.load_tile_info();
@@ -1076,6 +1294,7 @@ impl Ricoh2C02 {
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     pub fn process_prerender(&mut self) {
         if self.cycle == 1 {
             self.vblank = false;
@@ -1092,18 +1311,19 @@ impl Ricoh2C02 {
 
     pub fn process_vblank(&mut self) {
         if self.cycle == 1 {
-            self.vblank = true;
+            self.vblank = !self.suppress_vblank;
+            self.suppress_vblank = false;
             self.redraw = true;
-
-            if self.nmi_enable {
-                self.should_nmi = true;
-            }
         }
     }
 
     pub fn update_cycle(&mut self) {
         self.cycle += 1;
 
+        // A dot lasts eight of the twelve master clocks in a subcarrier cycle.
+        // The skipped dot on odd frames takes no time, so it doesn't count.
+        self.phase = (self.phase + 8) % 12;
+
         if self.cycle > PPU_LAST_CYCLE {
             self.cycle = 0;
             self.scanline += 1;
@@ -1117,9 +1337,14 @@ impl Ricoh2C02 {
 
                 self.odd = !self.odd;
             }
+
+            if self.scanline == 0 {
+                self.frame_phase = self.phase;
+            }
         }
     }
 
+    #[allow(clippy::needless_return)]
     pub fn vram_read(&mut self, address: u16) -> u8 {
         let address = address & 0x3fff;
 
@@ -1135,6 +1360,7 @@ impl Ricoh2C02 {
         return self.palette_read(address)
         }
 
+    #[allow(clippy::needless_return)]
     pub fn vram_write(&mut self, address: u16, value: u8) {
         let address = address & 0x3fff;
 
@@ -0,0 +1,189 @@
+use nes::ricoh2c02::palette;
+use nes::ricoh2c02::palette::NtscSettings;
+use nes::ricoh2c02::palette::Palette;
+use nes::ricoh2c02::palette::PALETTE_ENTRIES;
+use nes::SCREEN_HEIGHT;
+use nes::SCREEN_WIDTH;
+
+// The signal is synthesised at the PPU's master clock, twelve samples per
+// subcarrier cycle, which makes eight samples per dot.
+const SAMPLES_PER_DOT: usize = 8;
+const LINE_SAMPLES: usize = SCREEN_WIDTH * SAMPLES_PER_DOT;
+const DOTS_PER_LINE: usize = 341;
+
+// Decoded lines are read out every fourth sample, three per subcarrier cycle.
+const OUTPUT_STEP: usize = 4;
+pub const NTSC_WIDTH: usize = LINE_SAMPLES / OUTPUT_STEP;
+
+// Filter widths in samples. A full subcarrier cycle notches chroma out of
+// luma; the wider chroma filters are what make colours bleed.
+const COMPOSITE_LUMA_WIDTH: usize = 12;
+const SVIDEO_LUMA_WIDTH: usize = 4;
+const I_WIDTH: usize = 24;
+const Q_WIDTH: usize = 36;
+
+// Steps in the table that stands in for gamma correction.
+const GAMMA_STEPS: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NtscPreset {
+    // Palette colours as they are, just at the wider output resolution.
+    Rgb,
+    // Separate luma and chroma: colour bleeds but there are no artifacts.
+    SVideo,
+    // A single signal, with dot crawl and artifact colours.
+    Composite,
+}
+
+// Turns the PPU's framebuffer of palette indices and emphasis bits back into
+// the composite signal it would have sent to a TV and decodes that to RGB,
+// NTSC_WIDTH pixels wide.
+pub struct NtscFilter {
+    preset: NtscPreset,
+    settings: NtscSettings,
+    palette: Palette,
+
+    // Signal level of every palette entry at every phase, and its average.
+    levels: Box<[f32]>,
+    luma: Box<[f32]>,
+    carrier: [(f32, f32); 12],
+    gamma: Box<[u8]>,
+
+    signal: Vec<f32>,
+    y: Vec<f32>,
+    i: Vec<f32>,
+    q: Vec<f32>,
+    sums: Vec<f32>,
+}
+
+impl NtscFilter {
+    pub fn new(preset: NtscPreset, settings: &NtscSettings, palette: &Palette) -> NtscFilter {
+        let mut levels = vec![0.0; PALETTE_ENTRIES * 12];
+        let mut luma = vec![0.0; PALETTE_ENTRIES];
+
+        for index in 0..PALETTE_ENTRIES {
+            for phase in 0..12 {
+                levels[index * 12 + phase] = palette::level(index, phase);
+            }
+
+            luma[index] = levels[index * 12..(index + 1) * 12].iter().sum::<f32>() / 12.0;
+        }
+
+        let mut carrier = [(0.0, 0.0); 12];
+        for (phase, weights) in carrier.iter_mut().enumerate() {
+            *weights = palette::carrier(phase, settings);
+        }
+
+        let gamma: Vec<u8> = (0..=GAMMA_STEPS)
+            .map(|step| palette::encode(step as f32 / GAMMA_STEPS as f32, settings))
+            .collect();
+
+        NtscFilter {
+            preset,
+            settings: *settings,
+            palette: palette.clone(),
+            levels: levels.into_boxed_slice(),
+            luma: luma.into_boxed_slice(),
+            carrier,
+            gamma: gamma.into_boxed_slice(),
+            signal: vec![0.0; LINE_SAMPLES],
+            y: vec![0.0; LINE_SAMPLES],
+            i: vec![0.0; LINE_SAMPLES + 1],
+            q: vec![0.0; LINE_SAMPLES + 1],
+            sums: vec![0.0; LINE_SAMPLES + 1],
+        }
+    }
+
+    pub fn preset(&self) -> NtscPreset {
+        self.preset
+    }
+
+    // `phase` is the subcarrier phase the frame started on, from
+    // Nes::frame_phase. It changes from frame to frame, which is what makes
+    // the dots crawl.
+    pub fn draw_rgb(&mut self, framebuffer: &[u16], phase: u8, buffer: &mut [u8], pitch: usize) {
+        for row in 0..SCREEN_HEIGHT {
+            let pixels = &framebuffer[row * SCREEN_WIDTH..(row + 1) * SCREEN_WIDTH];
+            let line = &mut buffer[row * pitch..row * pitch + NTSC_WIDTH * 3];
+
+            if self.preset == NtscPreset::Rgb {
+                for (x, rgb) in line.chunks_mut(3).enumerate() {
+                    rgb.copy_from_slice(&self.palette.rgb(pixels[x * SCREEN_WIDTH / NTSC_WIDTH]));
+                }
+                continue;
+            }
+
+            // Pixel x is output on dot x + 1 of the line.
+            let start = (phase as usize + (row * DOTS_PER_LINE + 1) * SAMPLES_PER_DOT) % 12;
+            self.decode_line(pixels, start);
+
+            for (x, rgb) in line.chunks_mut(3).enumerate() {
+                let n = x * OUTPUT_STEP + OUTPUT_STEP / 2;
+                let y = self.y[n];
+                let i = window(&self.i, n, I_WIDTH);
+                let q = window(&self.q, n, Q_WIDTH);
+                let levels = palette::yiq_to_levels(y, i, q, &self.settings);
+
+                for (value, level) in rgb.iter_mut().zip(levels.iter()) {
+                    let step = (level.clamp(0.0, 1.0) * GAMMA_STEPS as f32 + 0.5) as usize;
+                    *value = self.gamma[step];
+                }
+            }
+        }
+    }
+
+    // Leaves luma for every sample in `y`, and running sums of the
+    // demodulated chroma in `i` and `q` for `window` to filter.
+    fn decode_line(&mut self, pixels: &[u16], start: usize) {
+        for (n, sample) in self.signal.iter_mut().enumerate() {
+            let index = (pixels[n / SAMPLES_PER_DOT] & 0x1ff) as usize;
+            *sample = self.levels[index * 12 + (start + n) % 12];
+        }
+
+        // Luma is a low-pass of the whole signal for composite, or of the
+        // luma alone for S-Video. Chroma is whatever is left over.
+        if self.preset == NtscPreset::Composite {
+            running_sum(self.signal.iter().copied(), &mut self.sums);
+            for (n, y) in self.y.iter_mut().enumerate() {
+                *y = window(&self.sums, n, COMPOSITE_LUMA_WIDTH);
+            }
+        } else {
+            let luma = &self.luma;
+            running_sum(pixels.iter()
+                .flat_map(|&pixel| [luma[(pixel & 0x1ff) as usize]; SAMPLES_PER_DOT]), &mut self.sums);
+            for (n, y) in self.y.iter_mut().enumerate() {
+                *y = window(&self.sums, n, SVIDEO_LUMA_WIDTH);
+            }
+        }
+
+        self.i[0] = 0.0;
+        self.q[0] = 0.0;
+
+        for n in 0..LINE_SAMPLES {
+            let chroma = if self.preset == NtscPreset::Composite {
+                self.signal[n] - self.y[n]
+            } else {
+                self.signal[n] - self.luma[(pixels[n / SAMPLES_PER_DOT] & 0x1ff) as usize]
+            };
+
+            let (cos, sin) = self.carrier[(start + n) % 12];
+            self.i[n + 1] = self.i[n] + chroma * cos;
+            self.q[n + 1] = self.q[n] + chroma * sin;
+        }
+    }
+}
+
+fn running_sum<I: IntoIterator<Item = f32>>(input: I, sums: &mut [f32]) {
+    sums[0] = 0.0;
+    for (n, sample) in input.into_iter().enumerate() {
+        sums[n + 1] = sums[n] + sample;
+    }
+}
+
+// Average of the `width` samples centred on sample `n`, from a running sum,
+// averaging fewer at the ends of the line.
+fn window(sums: &[f32], n: usize, width: usize) -> f32 {
+    let low = n.saturating_sub(width / 2);
+    let high = (low + width).min(sums.len() - 1);
+    (sums[high] - sums[low]) / (high - low) as f32
+}
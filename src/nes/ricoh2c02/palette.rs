@@ -0,0 +1,241 @@
+use std::error;
+use std::f32::consts::PI;
+use std::fmt;
+use std::io;
+use std::io::Read;
+
+pub const PALETTE_COLOURS: usize = 64;
+pub const PALETTE_ENTRIES: usize = 512;
+
+static DEFAULT_PALETTE: [u8; 192] = [
+    84, 84, 84,         0, 30, 116,         8, 16, 144,         48, 0, 136,         68, 0, 100,         92, 0, 48,          84, 4, 0,       60, 24, 0,
+    32, 42, 0,          8, 58, 0,           0, 64, 0,           0, 60, 0,           0, 50, 60,          0, 0, 0,            0, 0, 0,        0, 0, 0,
+    152, 150, 152,      8, 76, 196,         48, 50, 236,        92, 30, 228,        136, 20, 176,       160, 20, 100,       152,34, 32,     120, 60, 0,
+    84, 90, 0,          40, 114, 0,         8, 124, 0,          0, 118, 40,         0, 102, 120,        0, 0, 0,            0, 0, 0,        0, 0, 0,
+    236, 238, 236,      76, 154, 236,       120, 124, 236,      176, 98, 236,       228, 84, 236,       236, 88, 180,       236, 106, 100,  212, 136, 32,
+    160, 170, 0,        116, 196, 0,        76, 208, 32,        56, 204, 108,       56, 180, 204,       60, 60, 60,         0, 0, 0,        0, 0, 0,
+    236, 238, 236,      168, 204, 236,      188, 188, 236,      212, 178, 236,      236, 174, 236,      236, 174, 212,      236, 180, 176,  228, 196, 144,
+    204, 210, 120,      180, 222, 120,      168, 226, 144,      152, 226, 180,      160, 214, 228,      160, 162, 160,      0, 0, 0,        0, 0, 0
+];
+
+// Each emphasis bit in PPUMASK darkens the two colour channels it doesn't
+// name by about this much.
+const EMPHASIS_ATTENUATION: f32 = 0.816328;
+
+// Composite output levels of the 2C02 in volts, for luma 0-3 with the signal
+// low and then high, and the levels the decoder treats as black and white.
+const SIGNAL_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const SIGNAL_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+const SIGNAL_BLACK: f32 = 0.518;
+const SIGNAL_WHITE: f32 = 1.962;
+
+// Emphasis attenuates the composite signal itself, during the phases that
+// belong to the emphasised colour.
+const SIGNAL_ATTENUATION: f32 = 0.746;
+
+// Delay between the colour burst and colour 0's phase, in twelfths of a
+// subcarrier cycle, as a TV with its tint knob centred would see it.
+const PHASE_OFFSET: f32 = 3.9;
+
+#[derive(Debug)]
+pub enum PaletteError {
+    InvalidSize(usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PaletteError::InvalidSize(size) =>
+                write!(f, "palette is {} bytes, expected {} or {}", size, PALETTE_COLOURS * 3, PALETTE_ENTRIES * 3),
+            PaletteError::Io(ref e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for PaletteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            PaletteError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(e: io::Error) -> PaletteError {
+        PaletteError::Io(e)
+    }
+}
+
+// Adjustments for the generated NTSC palette. Hue is in degrees; the others
+// are factors where 1.0 changes nothing, except brightness which is an
+// offset. Gamma is that of the display the colours are meant for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NtscSettings {
+    pub hue: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub brightness: f32,
+    pub gamma: f32,
+}
+
+impl Default for NtscSettings {
+    fn default() -> Self {
+        NtscSettings {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 2.2,
+        }
+    }
+}
+
+// RGB for every combination of a 6-bit palette index and the three emphasis
+// bits, indexed the same way as the PPU's framebuffer.
+#[derive(Clone)]
+pub struct Palette {
+    rgb: Box<[u8]>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::from_colours(&DEFAULT_PALETTE)
+    }
+}
+
+impl Palette {
+    // Reads a .pal file: either 64 colours, which get emphasis applied here,
+    // or all 512 with emphasis already worked out.
+    pub fn from_pal(file: &mut dyn Read) -> Result<Palette, PaletteError> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        match data.len() {
+            size if size == PALETTE_COLOURS * 3 => Ok(Palette::from_colours(&data)),
+            size if size == PALETTE_ENTRIES * 3 => Ok(Palette { rgb: data.into_boxed_slice() }),
+            size => Err(PaletteError::InvalidSize(size)),
+        }
+    }
+
+    fn from_colours(colours: &[u8]) -> Palette {
+        let mut rgb = vec![0; PALETTE_ENTRIES * 3];
+
+        for (index, entry) in rgb.chunks_mut(3).enumerate() {
+            let colour = index & 0x3f;
+            let emphasis = index >> 6;
+
+            for (channel, value) in entry.iter_mut().enumerate() {
+                let mut level = colours[colour * 3 + channel] as f32;
+
+                for bit in 0..3 {
+                    if emphasis & (1 << bit) != 0 && bit != channel {
+                        level *= EMPHASIS_ATTENUATION;
+                    }
+                }
+
+                *value = level.round() as u8;
+            }
+        }
+
+        Palette { rgb: rgb.into_boxed_slice() }
+    }
+
+    // Works the palette out from the signal the 2C02 generates: a square wave
+    // between two levels whose phase against the colour burst picks the hue.
+    // Each entry is decoded to YIQ by sampling one cycle of the subcarrier at
+    // the PPU's twelve phases, then converted to RGB.
+    pub fn ntsc(settings: &NtscSettings) -> Palette {
+        let mut rgb = vec![0; PALETTE_ENTRIES * 3];
+
+        for (index, entry) in rgb.chunks_mut(3).enumerate() {
+            let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+
+            for phase in 0..12 {
+                let level = level(index, phase);
+                let (cos, sin) = carrier(phase, settings);
+
+                y += level;
+                i += level * cos;
+                q += level * sin;
+            }
+
+            entry.copy_from_slice(&yiq_to_rgb(y / 12.0, i / 12.0, q / 12.0, settings));
+        }
+
+        Palette { rgb: rgb.into_boxed_slice() }
+    }
+
+    pub fn rgb(&self, index: u16) -> [u8; 3] {
+        let address = (index as usize & (PALETTE_ENTRIES - 1)) * 3;
+        [self.rgb[address], self.rgb[address + 1], self.rgb[address + 2]]
+    }
+}
+
+// Signal level of palette entry `index` at a subcarrier phase, scaled so that
+// black is 0 and white is 1.
+pub fn level(index: usize, phase: usize) -> f32 {
+    (signal(index, phase) - SIGNAL_BLACK) / (SIGNAL_WHITE - SIGNAL_BLACK)
+}
+
+// The decoder's reference carrier at a phase, as (I, Q) weights.
+pub fn carrier(phase: usize, settings: &NtscSettings) -> (f32, f32) {
+    let angle = PI / 6.0 * (phase as f32 + PHASE_OFFSET - settings.hue / 30.0);
+    (angle.cos(), angle.sin())
+}
+
+pub fn yiq_to_rgb(y: f32, i: f32, q: f32, settings: &NtscSettings) -> [u8; 3] {
+    let levels = yiq_to_levels(y, i, q, settings);
+    [encode(levels[0], settings), encode(levels[1], settings), encode(levels[2], settings)]
+}
+
+// Red, green and blue levels before gamma, with the adjustments applied.
+pub fn yiq_to_levels(y: f32, i: f32, q: f32, settings: &NtscSettings) -> [f32; 3] {
+    let y = y * settings.contrast + settings.brightness;
+    let i = i * settings.contrast * settings.saturation;
+    let q = q * settings.contrast * settings.saturation;
+
+    [
+        y + 0.946882 * i + 0.623557 * q,
+        y - 0.274788 * i - 0.635691 * q,
+        y - 1.108545 * i + 1.709007 * q,
+    ]
+}
+
+// The signal is already gamma encoded for a 2.2 display.
+pub fn encode(level: f32, settings: &NtscSettings) -> u8 {
+    (level.clamp(0.0, 1.0).powf(2.2 / settings.gamma) * 255.0).round() as u8
+}
+
+// Output level of palette entry `index` (with emphasis bits) at one of the
+// twelve subcarrier phases.
+fn signal(index: usize, phase: usize) -> f32 {
+    let colour = index & 0x0f;
+    let emphasis = index >> 6;
+
+    // Colours $xE and $xF are black whatever the luma bits say.
+    let luma = if colour > 13 { 1 } else { (index >> 4) & 0x03 };
+
+    let in_phase = |hue: usize| (hue + phase) % 12 < 6;
+
+    // Colour 0 stays high and colours $xD-$xF stay low for the whole cycle.
+    let level = if colour == 0 {
+        SIGNAL_HIGH[luma]
+    } else if colour > 12 {
+        SIGNAL_LOW[luma]
+    } else if in_phase(colour) {
+        SIGNAL_HIGH[luma]
+    } else {
+        SIGNAL_LOW[luma]
+    };
+
+    // Red, green and blue emphasis line up with colours 0, 4 and 8.
+    if (emphasis & 1 != 0 && in_phase(0))
+    || (emphasis & 2 != 0 && in_phase(4))
+    || (emphasis & 4 != 0 && in_phase(8)) {
+        level * SIGNAL_ATTENUATION
+    } else {
+        level
+    }
+}
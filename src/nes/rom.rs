@@ -1,14 +1,166 @@
+use std::error;
+use std::fmt;
+use std::io;
 use std::io::Read;
 
 pub const ROM_CHR_BANK_SIZE:    usize = 8192;
 pub const ROM_HEADER_SIZE:      usize = 16;
 pub const ROM_PRG_BANK_SIZE:    usize = 16384;
+pub const ROM_TRAINER_SIZE:     usize = 512;
+
+#[derive(Debug)]
+pub enum RomError {
+    BadMagic,
+    TruncatedHeader,
+    BadPrgSize(usize),
+    TruncatedPrg { expected: usize, found: usize },
+    TruncatedChr { expected: usize, found: usize },
+    UnsupportedMapper(u16),
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::BadMagic => write!(f, "not an iNES file (bad header magic)"),
+            RomError::TruncatedHeader => write!(f, "file is too short to contain an iNES header"),
+            RomError::BadPrgSize(size) =>
+                write!(f, "PRG ROM size of {} bytes is not a whole number of 16 KiB banks", size),
+            RomError::TruncatedPrg { expected, found } =>
+                write!(f, "PRG ROM truncated: expected {} bytes, found {}", expected, found),
+            RomError::TruncatedChr { expected, found } =>
+                write!(f, "CHR ROM truncated: expected {} bytes, found {}", expected, found),
+            RomError::UnsupportedMapper(mapper) => write!(f, "unsupported mapper #{}", mapper),
+            RomError::Io(ref e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for RomError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            RomError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> RomError {
+        RomError::Io(e)
+    }
+}
+
+fn read_fully(file: &mut dyn Read, buffer: &mut [u8]) -> Result<usize, io::Error> {
+    let mut bytes_read = 0;
+
+    while bytes_read < buffer.len() {
+        match file.read(&mut buffer[bytes_read..]) {
+            Ok(0) => break,
+            Ok(n) => bytes_read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(bytes_read)
+}
+
+// Section sizes come straight from the header, so read through `take` rather
+// than allocating the claimed size up front.
+fn read_section(file: &mut dyn Read, size: usize) -> Result<Vec<u8>, io::Error> {
+    let mut data = Vec::new();
+    Read::take(&mut *file, size as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
 
 pub enum MirrorMode {
     Horizontal,
     Vertical,
     FourScreen,
-    OneScreen
+    OneScreen,
+    OneScreenUpper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RomFormat {
+    Archaic,
+    INes,
+    Nes20,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    Extended(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VsPpuType {
+    Rp2c03b,
+    Rp2c03g,
+    Rp2c04_0001,
+    Rp2c04_0002,
+    Rp2c04_0003,
+    Rp2c04_0004,
+    Rc2c03b,
+    Rc2c03c,
+    Rc2c05_01,
+    Rc2c05_02,
+    Rc2c05_03,
+    Rc2c05_04,
+    Rc2c05_05,
+    Unknown(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VsHardwareType {
+    UniSystem,
+    UniSystemRbiBaseball,
+    UniSystemTkoBoxing,
+    UniSystemSuperXevious,
+    UniSystemIceClimber,
+    DualSystem,
+    DualSystemRaidOnBungelingBay,
+    Unknown(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VsSystem {
+    pub ppu: VsPpuType,
+    pub hardware: VsHardwareType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExpansionDevice {
+    Unspecified,
+    StandardControllers,
+    FourScore,
+    FamicomFourPlayers,
+    VsSystem,
+    VsSystemReversed,
+    VsPinball,
+    VsZapper,
+    Zapper,
+    TwoZappers,
+    BandaiHyperShot,
+    PowerPadSideA,
+    PowerPadSideB,
+    FamilyTrainerSideA,
+    FamilyTrainerSideB,
+    ArkanoidVausNes,
+    ArkanoidVausFamicom,
+    Other(u8),
 }
 
 #[derive(Clone)]
@@ -18,57 +170,345 @@ pub struct INesHeader {
     chr: u8,
     flags6: u8,
     flags7: u8,
-    ram: u8,
+    flags8: u8,
     flags9: u8,
+    flags10: u8,
+    flags11: u8,
+    flags12: u8,
+    flags13: u8,
+    flags14: u8,
+    flags15: u8,
+}
+
+impl INesHeader {
+    fn new(header: &[u8; ROM_HEADER_SIZE]) -> INesHeader {
+        INesHeader {
+            magic: [header[0], header[1], header[2], header[3]],
+            prg: header[4],
+            chr: header[5],
+            flags6: header[6],
+            flags7: header[7],
+            flags8: header[8],
+            flags9: header[9],
+            flags10: header[10],
+            flags11: header[11],
+            flags12: header[12],
+            flags13: header[13],
+            flags14: header[14],
+            flags15: header[15],
+        }
+    }
+
+    fn format(&self) -> RomFormat {
+        if self.flags7 & 0x0c == 0x08 {
+            return RomFormat::Nes20;
+        }
+
+        // Headers written by old tools (e.g. "DiskDude!") leave garbage in
+        // bytes 7-15, in which case only the low mapper nibble can be trusted.
+        if self.flags7 & 0x0c == 0x00
+        && self.flags12 == 0 && self.flags13 == 0
+        && self.flags14 == 0 && self.flags15 == 0 {
+            return RomFormat::INes;
+        }
+
+        RomFormat::Archaic
+    }
+
+    fn rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+        if msb == 0x0f {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = ((lsb & 0x03) as usize * 2) + 1;
+
+            return 1usize.checked_shl(exponent)
+                .and_then(|size| size.checked_mul(multiplier))
+                .unwrap_or(usize::MAX);
+        }
+
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+
+    fn prg_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::rom_size(self.prg, self.flags9 & 0x0f, ROM_PRG_BANK_SIZE),
+            _ => self.prg as usize * ROM_PRG_BANK_SIZE,
+        }
+    }
+
+    fn chr_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::rom_size(self.chr, self.flags9 >> 4, ROM_CHR_BANK_SIZE),
+            _ => self.chr as usize * ROM_CHR_BANK_SIZE,
+        }
+    }
+
+    fn ram_size(shift: u8) -> usize {
+        if shift == 0 {
+            0
+        } else {
+            64 << shift
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Rom {
     header: INesHeader,
+    trainer: Option<Box<[u8]>>,
     prg: Box<[u8]>,
     chr: Box<[u8]>,
 }
 
 impl Rom {
-    pub fn new(file: &mut Read) -> Rom {
+    pub fn new(file: &mut dyn Read) -> Result<Rom, RomError> {
         let mut header = [0; ROM_HEADER_SIZE];
-        let mut bytes_read = file.read(&mut header[0..]).unwrap();
 
-        let ines_header = INesHeader {
-            magic: [header[0], header[1], header[2], header[3]],
-            prg: header[4],
-            chr: header[5],
-            flags6: header[6],
-            flags7: header[7],
-            ram: header[8],
-            flags9: header[9],
-        };
+        if read_fully(file, &mut header[0..])? != ROM_HEADER_SIZE {
+            return Err(RomError::TruncatedHeader);
+        }
+
+        let ines_header = INesHeader::new(&header);
 
         if ines_header.magic != *b"NES\x1a" {
-            panic!("invalid iNES header");
+            return Err(RomError::BadMagic);
+        }
+
+        let mut trainer = None;
+
+        if ines_header.flags6 & 0x04 != 0 {
+            let mut data = vec![0u8; ROM_TRAINER_SIZE].into_boxed_slice();
+
+            if read_fully(file, &mut data[0..])? != ROM_TRAINER_SIZE {
+                return Err(RomError::TruncatedHeader);
+            }
+
+            trainer = Some(data);
         }
 
-        let prg_size = ines_header.prg as usize * ROM_PRG_BANK_SIZE;
-        let mut prg = vec![0u8; prg_size].into_boxed_slice();
-        bytes_read += file.read(&mut prg[0..]).unwrap();
+        // Every mapper addresses PRG in whole 16 KiB banks, so an empty or
+        // ragged size (possible with the NES 2.0 exponent form) is unusable.
+        let prg_size = ines_header.prg_size();
+
+        if prg_size == 0 || !prg_size.is_multiple_of(ROM_PRG_BANK_SIZE) {
+            return Err(RomError::BadPrgSize(prg_size));
+        }
 
-        let chr_size = ines_header.chr as usize * ROM_CHR_BANK_SIZE;
-        let mut chr = vec![0u8; chr_size].into_boxed_slice();
-        bytes_read += file.read(&mut chr[0..]).unwrap();
+        let prg = read_section(file, prg_size)?;
 
-        if bytes_read != (ROM_HEADER_SIZE + prg_size + chr_size) {
-            panic!("unexpected EOF");
+        if prg.len() != prg_size {
+            return Err(RomError::TruncatedPrg { expected: prg_size, found: prg.len() });
         }
 
-        Rom {
+        let chr_size = ines_header.chr_size();
+        let chr = read_section(file, chr_size)?;
+
+        if chr.len() != chr_size {
+            return Err(RomError::TruncatedChr { expected: chr_size, found: chr.len() });
+        }
+
+        Ok(Rom {
             header: ines_header,
-            prg: prg,
-            chr: chr,
+            trainer,
+            prg: prg.into_boxed_slice(),
+            chr: chr.into_boxed_slice(),
+        })
+    }
+
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+
+        for byte in self.prg.iter().chain(self.chr.iter()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
         }
+
+        hash
     }
 
-    pub fn mapper(&self) -> u8 {
-        (self.header.flags6 >> 4) | (self.header.flags7 & 0xf0)
+    pub fn format(&self) -> RomFormat {
+        self.header.format()
+    }
+
+    pub fn mapper(&self) -> u16 {
+        let mut mapper = (self.header.flags6 >> 4) as u16;
+
+        match self.format() {
+            RomFormat::Archaic => (),
+            RomFormat::INes => mapper |= (self.header.flags7 & 0xf0) as u16,
+            RomFormat::Nes20 => {
+                mapper |= (self.header.flags7 & 0xf0) as u16;
+                mapper |= ((self.header.flags8 & 0x0f) as u16) << 8;
+            },
+        }
+
+        mapper
+    }
+
+    pub fn submapper(&self) -> u8 {
+        match self.format() {
+            RomFormat::Nes20 => self.header.flags8 >> 4,
+            _ => 0,
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        (self.header.flags6 & 0x02) != 0
+    }
+
+    pub fn trainer(&self) -> Option<&[u8]> {
+        self.trainer.as_ref().map(|trainer| &trainer[..])
+    }
+
+    pub fn prg_rom_size(&self) -> usize {
+        self.prg.len()
+    }
+
+    pub fn chr_rom_size(&self) -> usize {
+        self.chr.len()
+    }
+
+    pub fn prg_ram_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::ram_size(self.header.flags10 & 0x0f),
+            _ if self.has_battery() => 0,
+            _ => self.ines_prg_ram_size(),
+        }
+    }
+
+    pub fn prg_nvram_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::ram_size(self.header.flags10 >> 4),
+            _ if self.has_battery() => self.ines_prg_ram_size(),
+            _ => 0,
+        }
+    }
+
+    pub fn chr_ram_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::ram_size(self.header.flags11 & 0x0f),
+            _ if self.chr.is_empty() => ROM_CHR_BANK_SIZE,
+            _ => 0,
+        }
+    }
+
+    pub fn chr_nvram_size(&self) -> usize {
+        match self.format() {
+            RomFormat::Nes20 => INesHeader::ram_size(self.header.flags11 >> 4),
+            _ => 0,
+        }
+    }
+
+    fn ines_prg_ram_size(&self) -> usize {
+        match self.format() {
+            RomFormat::INes if self.header.flags8 != 0 => self.header.flags8 as usize * 0x2000,
+            _ => 0x2000,
+        }
+    }
+
+    pub fn timing(&self) -> Timing {
+        match self.format() {
+            RomFormat::Nes20 => match self.header.flags12 & 0x03 {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultiRegion,
+                3 => Timing::Dendy,
+                _ => unreachable!()
+            },
+            RomFormat::INes if self.header.flags9 & 0x01 != 0 => Timing::Pal,
+            _ => Timing::Ntsc,
+        }
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        if self.format() == RomFormat::Archaic {
+            return ConsoleType::Nes;
+        }
+
+        match self.header.flags7 & 0x03 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            3 if self.format() == RomFormat::Nes20 => ConsoleType::Extended(self.header.flags13 & 0x0f),
+            3 => ConsoleType::Nes,
+            _ => unreachable!()
+        }
+    }
+
+    pub fn vs_system(&self) -> Option<VsSystem> {
+        if self.console_type() != ConsoleType::VsSystem {
+            return None;
+        }
+
+        if self.format() != RomFormat::Nes20 {
+            return Some(VsSystem {
+                ppu: VsPpuType::Rp2c03b,
+                hardware: VsHardwareType::UniSystem,
+            });
+        }
+
+        let ppu = match self.header.flags13 & 0x0f {
+            0x0 => VsPpuType::Rp2c03b,
+            0x1 => VsPpuType::Rp2c03g,
+            0x2 => VsPpuType::Rp2c04_0001,
+            0x3 => VsPpuType::Rp2c04_0002,
+            0x4 => VsPpuType::Rp2c04_0003,
+            0x5 => VsPpuType::Rp2c04_0004,
+            0x6 => VsPpuType::Rc2c03b,
+            0x7 => VsPpuType::Rc2c03c,
+            0x8 => VsPpuType::Rc2c05_01,
+            0x9 => VsPpuType::Rc2c05_02,
+            0xa => VsPpuType::Rc2c05_03,
+            0xb => VsPpuType::Rc2c05_04,
+            0xc => VsPpuType::Rc2c05_05,
+            n => VsPpuType::Unknown(n),
+        };
+
+        let hardware = match self.header.flags13 >> 4 {
+            0x0 => VsHardwareType::UniSystem,
+            0x1 => VsHardwareType::UniSystemRbiBaseball,
+            0x2 => VsHardwareType::UniSystemTkoBoxing,
+            0x3 => VsHardwareType::UniSystemSuperXevious,
+            0x4 => VsHardwareType::UniSystemIceClimber,
+            0x5 => VsHardwareType::DualSystem,
+            0x6 => VsHardwareType::DualSystemRaidOnBungelingBay,
+            n => VsHardwareType::Unknown(n),
+        };
+
+        Some(VsSystem { ppu, hardware })
+    }
+
+    pub fn misc_roms(&self) -> u8 {
+        match self.format() {
+            RomFormat::Nes20 => self.header.flags14 & 0x03,
+            _ => 0,
+        }
+    }
+
+    pub fn expansion_device(&self) -> ExpansionDevice {
+        if self.format() != RomFormat::Nes20 {
+            return ExpansionDevice::Unspecified;
+        }
+
+        match self.header.flags15 & 0x3f {
+            0x00 => ExpansionDevice::Unspecified,
+            0x01 => ExpansionDevice::StandardControllers,
+            0x02 => ExpansionDevice::FourScore,
+            0x03 => ExpansionDevice::FamicomFourPlayers,
+            0x04 => ExpansionDevice::VsSystem,
+            0x05 => ExpansionDevice::VsSystemReversed,
+            0x06 => ExpansionDevice::VsPinball,
+            0x07 => ExpansionDevice::VsZapper,
+            0x08 => ExpansionDevice::Zapper,
+            0x09 => ExpansionDevice::TwoZappers,
+            0x0a => ExpansionDevice::BandaiHyperShot,
+            0x0b => ExpansionDevice::PowerPadSideA,
+            0x0c => ExpansionDevice::PowerPadSideB,
+            0x0d => ExpansionDevice::FamilyTrainerSideA,
+            0x0e => ExpansionDevice::FamilyTrainerSideB,
+            0x0f => ExpansionDevice::ArkanoidVausNes,
+            0x10 => ExpansionDevice::ArkanoidVausFamicom,
+            n => ExpansionDevice::Other(n),
+        }
     }
 
     pub fn mirroring(&self) -> MirrorMode {
@@ -0,0 +1,184 @@
+use std::error;
+use std::fmt;
+
+pub const STATE_MAGIC: [u8; 4] = *b"RNST";
+pub const STATE_VERSION: u32 = 10;
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    RomMismatch,
+    Truncated,
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) =>
+                write!(f, "unsupported save state version {} (expected {})", version, STATE_VERSION),
+            StateError::RomMismatch => write!(f, "save state was made with a different ROM"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl error::Error for StateError {}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter {
+            data: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, value: &[u8]) {
+        self.write_u32(value.len() as u32);
+        self.data.extend_from_slice(value);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader {
+            data,
+            position: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() - self.position < length {
+            return Err(StateError::Truncated);
+        }
+
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    pub fn read_bytes(&mut self, value: &mut [u8]) -> Result<(), StateError> {
+        if self.read_u32()? as usize != value.len() {
+            return Err(StateError::Corrupt);
+        }
+
+        value.copy_from_slice(self.take(value.len())?);
+        Ok(())
+    }
+}
+
+pub fn write_header(state: &mut StateWriter, rom_hash: u64) {
+    for byte in STATE_MAGIC.iter() {
+        state.write_u8(*byte);
+    }
+
+    state.write_u32(STATE_VERSION);
+    state.write_u64(rom_hash);
+}
+
+pub fn read_header(state: &mut StateReader, rom_hash: u64) -> Result<(), StateError> {
+    let mut magic = [0; 4];
+    for byte in magic.iter_mut() {
+        *byte = state.read_u8()?;
+    }
+
+    if magic != STATE_MAGIC {
+        return Err(StateError::BadMagic);
+    }
+
+    let version = state.read_u32()?;
+    if version != STATE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+
+    if state.read_u64()? != rom_hash {
+        return Err(StateError::RomMismatch);
+    }
+
+    Ok(())
+}
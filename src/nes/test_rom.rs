@@ -0,0 +1,117 @@
+use nes::Nes;
+
+// blargg's test ROMs report through cartridge RAM: a status byte at $6000,
+// the signature DE B0 61 at $6001 once the rest is valid, and a
+// zero-terminated message from $6004.
+pub const STATUS_ADDRESS: u16 = 0x6000;
+pub const SIGNATURE_ADDRESS: u16 = 0x6001;
+pub const TEXT_ADDRESS: u16 = 0x6004;
+
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const TEXT_LIMIT: u16 = 0x1ffc;
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET: u8 = 0x81;
+
+// The ROM asks for the reset button to be held off for at least 100 ms.
+const RESET_DELAY_FRAMES: u64 = 6;
+
+pub const DEFAULT_FRAME_LIMIT: u64 = 60 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestOutcome {
+    Passed,
+    // The result code the ROM finished with; which test failed is in the text.
+    Failed(u8),
+    // The signature appeared but the ROM didn't finish within the limit.
+    Timeout,
+    // The signature never appeared, so the ROM may not use the protocol.
+    NoSignature,
+    Halted(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    pub outcome: TestOutcome,
+    pub text: String,
+    pub frames: u64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.outcome == TestOutcome::Passed
+    }
+}
+
+fn has_signature(nes: &Nes) -> bool {
+    SIGNATURE.iter().enumerate().all(|(i, &byte)| nes.peek(SIGNATURE_ADDRESS + i as u16) == byte)
+}
+
+fn read_text(nes: &Nes) -> String {
+    let text: Vec<u8> = (0..TEXT_LIMIT)
+        .map(|i| nes.peek(TEXT_ADDRESS + i))
+        .take_while(|&byte| byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+// Runs a powered-on machine until the ROM reports a result, giving up after
+// `frame_limit` frames. Requests for a reset are honoured with a soft reset.
+pub fn run_test_rom(nes: &mut Nes, frame_limit: u64) -> TestResult {
+    let start = nes.frame();
+    let mut signature = false;
+    let mut reset_at = None;
+    let mut reset_done = false;
+
+    let outcome = loop {
+        let frames = nes.frame() - start;
+
+        if frames >= frame_limit {
+            break if signature { TestOutcome::Timeout } else { TestOutcome::NoSignature };
+        }
+
+        nes.run_frame();
+
+        if let Some(opcode) = nes.jam_opcode() {
+            break TestOutcome::Halted(opcode);
+        }
+
+        if !has_signature(nes) {
+            continue;
+        }
+
+        signature = true;
+
+        match nes.peek(STATUS_ADDRESS) {
+            STATUS_RUNNING => reset_done = false,
+
+            STATUS_RESET => {
+                // The status stays at $81 until the ROM is back up after the
+                // reset, so only one reset is issued per request.
+                if reset_done {
+                    continue;
+                }
+
+                match reset_at {
+                    None => reset_at = Some(nes.frame() + RESET_DELAY_FRAMES),
+                    Some(frame) if nes.frame() >= frame => {
+                        nes.reset();
+                        reset_at = None;
+                        reset_done = true;
+                    },
+                    Some(_) => {},
+                }
+            },
+
+            0 => break TestOutcome::Passed,
+            code => break TestOutcome::Failed(code),
+        }
+    };
+
+    TestResult {
+        outcome,
+        text: read_text(nes),
+        frames: nes.frame() - start,
+    }
+}
@@ -0,0 +1,122 @@
+use nes::ricoh2a03::Ricoh2A03;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceCondition {
+    Pc(u16),
+    Frame(u64),
+}
+
+impl TraceCondition {
+    fn matches(&self, pc: u16, frame: u64) -> bool {
+        match *self {
+            TraceCondition::Pc(address) => pc == address,
+            TraceCondition::Frame(number) => frame >= number,
+        }
+    }
+}
+
+// Writes a nestest.log style line for every instruction the CPU runs while
+// enabled. Tracing can be switched on and off directly, or armed to start and
+// stop when the CPU reaches an address or the machine reaches a frame.
+pub struct Tracer {
+    output: Box<dyn Write + Send>,
+    enabled: bool,
+    start: Option<TraceCondition>,
+    stop: Option<TraceCondition>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write + Send>) -> Tracer {
+        Tracer {
+            output,
+            enabled: true,
+            start: None,
+            stop: None,
+            error: None,
+        }
+    }
+
+    pub fn create(path: &Path) -> io::Result<Tracer> {
+        let file = File::create(path)?;
+        Ok(Tracer::new(Box::new(BufWriter::new(file))))
+    }
+
+    // Holds the tracer off until the condition is met.
+    pub fn start_at(&mut self, condition: TraceCondition) {
+        self.start = Some(condition);
+        self.enabled = false;
+    }
+
+    pub fn stop_at(&mut self, condition: TraceCondition) {
+        self.stop = Some(condition);
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.start = None;
+
+        if !enabled {
+            self.flush();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        let enabled = !self.enabled;
+        self.set_enabled(enabled);
+    }
+
+    // The first write error, after which the tracer disables itself.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(e) = self.output.flush() {
+            self.fail(e);
+        }
+    }
+
+    pub fn trace(&mut self, cpu: &Ricoh2A03, frame: u64) {
+        let pc = cpu.pc();
+
+        if let Some(condition) = self.start {
+            if condition.matches(pc, frame) {
+                self.start = None;
+                self.enabled = true;
+            }
+        }
+
+        if let Some(condition) = self.stop {
+            if self.enabled && condition.matches(pc, frame) {
+                self.stop = None;
+                self.set_enabled(false);
+            }
+        }
+
+        if !self.enabled || self.error.is_some() {
+            return;
+        }
+
+        if let Err(e) = writeln!(self.output, "{}", cpu.trace()) {
+            self.fail(e);
+        }
+    }
+
+    fn fail(&mut self, error: io::Error) {
+        self.enabled = false;
+
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use rnes::nes::rewind::REWIND_DEFAULT_BUDGET;
+use rnes::nes::rewind::REWIND_DEFAULT_INTERVAL;
+use rnes::nes::test_rom::DEFAULT_FRAME_LIMIT;
+use rnes::nes::ricoh2a03::apu::filter::AudioProfile;
+use rnes::nes::ricoh2a03::apu::mixer::Channel;
+use rnes::nes::ricoh2a03::apu::mixer::ChannelSettings;
+use rnes::nes::ricoh2c02::ntsc::NtscPreset;
+use rnes::nes::ricoh2c02::palette::NtscSettings;
+use rnes::TraceCondition;
+use pacing::PacingMode;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub enum TraceStart {
+    Condition(TraceCondition),
+    Key,
+}
+
+pub enum PaletteSource {
+    Default,
+    File(PathBuf),
+    Ntsc(NtscSettings),
+}
+
+pub struct Options {
+    pub rom_path: PathBuf,
+    pub save_dir: Option<PathBuf>,
+    pub rewind_interval: u64,
+    pub rewind_budget: usize,
+    pub trace_path: Option<PathBuf>,
+    pub trace_start: Option<TraceStart>,
+    pub trace_stop: Option<TraceCondition>,
+    pub nestest: bool,
+    pub nestest_log: Option<PathBuf>,
+    pub palette: PaletteSource,
+    pub ntsc_filter: Option<NtscPreset>,
+    pub audio_profile: AudioProfile,
+    pub pacing: PacingMode,
+    pub channels: [ChannelSettings; 6],
+}
+
+pub struct TestOptions {
+    pub rom_path: PathBuf,
+    pub frame_limit: u64,
+}
+
+pub fn usage() -> &'static str {
+    "usage: rnes [--save-dir <dir>] [--rewind-interval <frames>] [--rewind-budget <MiB>]\n\
+     \x20           [--trace <file>] [--trace-start pc:<hex>|frame:<n>|key]\n\
+     \x20           [--trace-stop pc:<hex>|frame:<n>] [--nestest [--nestest-log <file>]]\n\
+     \x20           [--palette <file.pal>|ntsc] [--hue <degrees>] [--saturation <n>]\n\
+     \x20           [--contrast <n>] [--brightness <n>] [--gamma <n>]\n\
+     \x20           [--ntsc rgb|svideo|composite] [--audio-profile nes|famicom]\n\
+     \x20           [--sync audio|vsync|sleep] [--mute <channel>]\n\
+     \x20           [--volume <channel>=<n>] [--pan <channel>=<n>]\n\
+     \x20           <rom.nes>\n\
+     \x20      rnes test [--frames <n>] <rom.nes>"
+}
+
+fn parse_number<T: FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or(format!("{} requires a value", option))?;
+    value.parse().map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+fn parse_channel(option: &str, name: &str) -> Result<Channel, String> {
+    Channel::ALL.iter()
+        .find(|channel| channel.name() == name)
+        .copied()
+        .ok_or(format!("unknown channel for {}: {}", option, name))
+}
+
+// Parses `<channel>=<value>`.
+fn parse_channel_value(option: &str, value: Option<String>) -> Result<(Channel, f32), String> {
+    let value = value.ok_or(format!("{} requires <channel>=<value>", option))?;
+    let (name, number) = value.split_once('=').ok_or(format!("{} requires <channel>=<value>", option))?;
+    let number = number.parse().map_err(|_| format!("invalid value for {}: {}", option, value))?;
+
+    Ok((parse_channel(option, name)?, number))
+}
+
+fn parse_trace_condition(option: &str, value: Option<String>) -> Result<TraceCondition, String> {
+    let value = value.ok_or(format!("{} requires a condition", option))?;
+    let invalid = || format!("invalid condition for {}: {}", option, value);
+
+    if let Some(address) = value.strip_prefix("pc:") {
+        let address = address.trim_start_matches('$');
+        return u16::from_str_radix(address, 16).map(TraceCondition::Pc).map_err(|_| invalid());
+    }
+
+    if let Some(frame) = value.strip_prefix("frame:") {
+        return frame.parse().map(TraceCondition::Frame).map_err(|_| invalid());
+    }
+
+    Err(invalid())
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut rom_path = None;
+        let mut save_dir = None;
+        let mut rewind_interval = REWIND_DEFAULT_INTERVAL;
+        let mut rewind_budget = REWIND_DEFAULT_BUDGET;
+        let mut trace_path = None;
+        let mut trace_start = None;
+        let mut trace_stop = None;
+        let mut nestest = false;
+        let mut nestest_log = None;
+        let mut palette_path = None;
+        let mut ntsc = None;
+        let mut ntsc_filter = None;
+        let mut audio_profile = AudioProfile::Nes;
+        let mut pacing = PacingMode::Audio;
+        let mut channels = [ChannelSettings::default(); 6];
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--save-dir" => {
+                    let dir = args.next().ok_or("--save-dir requires a directory")?;
+                    save_dir = Some(PathBuf::from(dir));
+                },
+
+                "--rewind-interval" => {
+                    rewind_interval = parse_number(&arg, args.next())?;
+                },
+
+                "--rewind-budget" => {
+                    let megabytes: usize = parse_number(&arg, args.next())?;
+                    rewind_budget = megabytes * 1024 * 1024;
+                },
+
+                "--trace" => {
+                    let path = args.next().ok_or("--trace requires a file")?;
+                    trace_path = Some(PathBuf::from(path));
+                },
+
+                "--trace-start" => {
+                    let value = args.next();
+
+                    trace_start = Some(match value.as_deref() {
+                        Some("key") => TraceStart::Key,
+                        _ => TraceStart::Condition(parse_trace_condition(&arg, value)?),
+                    });
+                },
+
+                "--trace-stop" => {
+                    trace_stop = Some(parse_trace_condition(&arg, args.next())?);
+                },
+
+                "--nestest" => {
+                    nestest = true;
+                },
+
+                "--nestest-log" => {
+                    let path = args.next().ok_or("--nestest-log requires a file")?;
+                    nestest = true;
+                    nestest_log = Some(PathBuf::from(path));
+                },
+
+                "--palette" => {
+                    match args.next().ok_or("--palette requires a file or ntsc")?.as_str() {
+                        "ntsc" => { ntsc.get_or_insert_with(NtscSettings::default); },
+                        path => palette_path = Some(PathBuf::from(path)),
+                    }
+                },
+
+                "--ntsc" => {
+                    ntsc_filter = Some(match args.next().as_deref() {
+                        Some("rgb") => NtscPreset::Rgb,
+                        Some("svideo") => NtscPreset::SVideo,
+                        Some("composite") => NtscPreset::Composite,
+                        Some(value) => return Err(format!("invalid value for --ntsc: {}", value)),
+                        None => return Err("--ntsc requires rgb, svideo or composite".to_string()),
+                    });
+                },
+
+                "--audio-profile" => {
+                    audio_profile = match args.next().as_deref() {
+                        Some("nes") => AudioProfile::Nes,
+                        Some("famicom") => AudioProfile::Famicom,
+                        Some(value) => return Err(format!("invalid value for --audio-profile: {}", value)),
+                        None => return Err("--audio-profile requires nes or famicom".to_string()),
+                    };
+                },
+
+                "--sync" => {
+                    pacing = match args.next().as_deref() {
+                        Some("audio") => PacingMode::Audio,
+                        Some("vsync") => PacingMode::Vsync,
+                        Some("sleep") => PacingMode::Sleep,
+                        Some(value) => return Err(format!("invalid value for --sync: {}", value)),
+                        None => return Err("--sync requires audio, vsync or sleep".to_string()),
+                    };
+                },
+
+                "--mute" => {
+                    let name = args.next().ok_or("--mute requires a channel")?;
+                    channels[parse_channel(&arg, &name)? as usize].enabled = false;
+                },
+
+                "--volume" => {
+                    let (channel, volume) = parse_channel_value(&arg, args.next())?;
+                    channels[channel as usize].volume = volume;
+                },
+
+                "--pan" => {
+                    let (channel, pan) = parse_channel_value(&arg, args.next())?;
+                    channels[channel as usize].pan = pan;
+                },
+
+                "--hue" => {
+                    ntsc.get_or_insert_with(NtscSettings::default).hue = parse_number(&arg, args.next())?;
+                },
+
+                "--saturation" => {
+                    ntsc.get_or_insert_with(NtscSettings::default).saturation = parse_number(&arg, args.next())?;
+                },
+
+                "--contrast" => {
+                    ntsc.get_or_insert_with(NtscSettings::default).contrast = parse_number(&arg, args.next())?;
+                },
+
+                "--brightness" => {
+                    ntsc.get_or_insert_with(NtscSettings::default).brightness = parse_number(&arg, args.next())?;
+                },
+
+                "--gamma" => {
+                    ntsc.get_or_insert_with(NtscSettings::default).gamma = parse_number(&arg, args.next())?;
+                },
+
+                _ if arg.starts_with("--") => {
+                    return Err(format!("unknown option {}", arg));
+                },
+
+                _ => {
+                    if rom_path.is_some() {
+                        return Err(format!("unexpected argument {}", arg));
+                    }
+
+                    rom_path = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        let palette = match (palette_path, ntsc) {
+            (Some(_), Some(_)) => return Err("palette adjustments only apply to --palette ntsc".to_string()),
+            (Some(path), None) => PaletteSource::File(path),
+            (None, Some(settings)) => PaletteSource::Ntsc(settings),
+            (None, None) => PaletteSource::Default,
+        };
+
+        Ok(Options {
+            rom_path: rom_path.ok_or("no ROM file given")?,
+            save_dir,
+            rewind_interval,
+            rewind_budget,
+            trace_path,
+            trace_start,
+            trace_stop,
+            nestest,
+            nestest_log,
+            palette,
+            ntsc_filter,
+            audio_profile,
+            pacing,
+            channels,
+        })
+    }
+}
+
+impl TestOptions {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<TestOptions, String> {
+        let mut rom_path = None;
+        let mut frame_limit = DEFAULT_FRAME_LIMIT;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--frames" => {
+                    frame_limit = parse_number(&arg, args.next())?;
+                },
+
+                _ if arg.starts_with("--") => {
+                    return Err(format!("unknown option {}", arg));
+                },
+
+                _ => {
+                    if rom_path.is_some() {
+                        return Err(format!("unexpected argument {}", arg));
+                    }
+
+                    rom_path = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        Ok(TestOptions {
+            rom_path: rom_path.ok_or("no ROM file given")?,
+            frame_limit,
+        })
+    }
+}
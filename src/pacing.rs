@@ -0,0 +1,106 @@
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+// NTSC frames per second: 1789773 CPU cycles over 29780.5 per frame.
+pub const FRAME_RATE: f64 = 60.0988;
+
+// Queued audio to aim for, in milliseconds.
+const AUDIO_LATENCY: u32 = 50;
+
+// How far the audio rate is stretched at most, when the queue is empty or
+// twice the target.
+const MAX_RATE_ADJUST: f64 = 0.005;
+
+// Sleeps wake up late by up to this much, so the end of a wait spins.
+const SPIN_TIME: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PacingMode {
+    // Run whenever the audio queue drains back to its target.
+    Audio,
+    // Let presenting the frame block until the display's next refresh.
+    Vsync,
+    // Sleep until each frame is due.
+    Sleep,
+}
+
+pub struct Pacer {
+    mode: PacingMode,
+    frame_time: Duration,
+    deadline: Instant,
+    audio_target: u32,
+}
+
+impl Pacer {
+    pub fn new(mode: PacingMode, sample_rate: u32) -> Pacer {
+        Pacer {
+            mode,
+            frame_time: Duration::from_secs_f64(1.0 / FRAME_RATE),
+            deadline: Instant::now(),
+            audio_target: sample_rate * AUDIO_LATENCY / 1000,
+        }
+    }
+
+    pub fn mode(&self) -> PacingMode {
+        self.mode
+    }
+
+    pub fn audio_target(&self) -> u32 {
+        self.audio_target
+    }
+
+    // Dynamic rate control: the ratio to make the next frame's samples at,
+    // a little over 1 when fewer than the target are queued and a little
+    // under when more are, so the queue settles at the target instead of
+    // running dry or filling up as the clocks drift apart.
+    pub fn rate_adjust(&self, queued: u32) -> f64 {
+        let fill = (queued as f64 / (2 * self.audio_target) as f64).min(1.0);
+        1.0 + MAX_RATE_ADJUST * (1.0 - 2.0 * fill)
+    }
+
+    // Waits for the next frame after one that queued audio. In audio mode
+    // that is when `queued` drops back to the target, or a frame late at
+    // most in case the device has stalled.
+    pub fn wait_for_audio<F: Fn() -> u32>(&mut self, queued: F) {
+        if self.mode != PacingMode::Audio {
+            return self.wait();
+        }
+
+        let timeout = Instant::now() + 2 * self.frame_time;
+
+        while queued() > self.audio_target && Instant::now() < timeout {
+            thread::sleep(SPIN_TIME);
+        }
+
+        self.deadline = Instant::now() + self.frame_time;
+    }
+
+    // Waits for the next frame. Vsync has already waited while presenting,
+    // and without audio the audio mode falls back to sleeping.
+    pub fn wait(&mut self) {
+        if self.mode == PacingMode::Vsync {
+            return;
+        }
+
+        let now = Instant::now();
+
+        // After a stall, start again from now rather than rushing to catch
+        // up on the frames that were missed.
+        if now > self.deadline + self.frame_time {
+            self.deadline = now;
+        }
+
+        if let Some(remaining) = self.deadline.checked_duration_since(now) {
+            if remaining > SPIN_TIME {
+                thread::sleep(remaining - SPIN_TIME);
+            }
+
+            while Instant::now() < self.deadline {
+                thread::yield_now();
+            }
+        }
+
+        self.deadline += self.frame_time;
+    }
+}
@@ -1,7 +1,35 @@
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::path::Path;
+use std::path::PathBuf;
 
 pub fn open_file<P: AsRef<Path>>(path: P) -> Result<File, io::Error> {
     File::open(path)
-}
\ No newline at end of file
+}
+
+pub fn save_path(rom_path: &Path, save_dir: Option<&Path>, extension: &str) -> PathBuf {
+    let mut path = match save_dir {
+        Some(dir) => dir.join(rom_path.file_name().unwrap_or_default()),
+        None => rom_path.to_path_buf(),
+    };
+
+    path.set_extension(extension);
+    path
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, io::Error> {
+    fs::read(path)
+}
+
+pub fn write_file<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), io::Error> {
+    let path = path.as_ref();
+
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+
+    fs::write(path, data)
+}
@@ -0,0 +1,63 @@
+// Battery-backed PRG RAM on MMC1, saved and restored through the Nes API.
+
+extern crate rnes;
+
+use rnes::nes::rom::ROM_PRG_BANK_SIZE;
+use rnes::{Nes, Rom};
+
+// Two PRG banks and CHR RAM, with the battery flag set. At power on the last
+// bank is fixed at $C000, where the program increments the byte at $6000 and
+// then disables PRG RAM before trying to overwrite it.
+fn mmc1_rom() -> Rom {
+    let mut code = vec![
+        0xad, 0x00, 0x60,   // LDA $6000
+        0x18,               // CLC
+        0x69, 0x01,         // ADC #$01
+        0x8d, 0x00, 0x60,   // STA $6000
+        0xa9, 0x00,         // LDA #$00
+        0x8d, 0x00, 0xe0,   // STA $E000
+        0x8d, 0x00, 0xe0,   // STA $E000
+        0x8d, 0x00, 0xe0,   // STA $E000
+        0x8d, 0x00, 0xe0,   // STA $E000
+        0xa9, 0x01,         // LDA #$01
+        0x8d, 0x00, 0xe0,   // STA $E000 (PRG bank = $10, RAM disabled)
+        0xa9, 0xff,         // LDA #$FF
+        0x8d, 0x00, 0x60,   // STA $6000 (ignored)
+    ];
+    let spin = 0xc000 + code.len() as u16;
+    code.extend(&[0x4c, spin as u8, (spin >> 8) as u8]);
+
+    let mut prg = vec![0u8; 2 * ROM_PRG_BANK_SIZE];
+    prg[ROM_PRG_BANK_SIZE..ROM_PRG_BANK_SIZE + code.len()].copy_from_slice(&code);
+    for vector in 0..3 {
+        let offset = prg.len() - 6 + vector * 2;
+        let target = if vector == 1 { 0xc000 } else { spin };
+        prg[offset] = target as u8;
+        prg[offset + 1] = (target >> 8) as u8;
+    }
+
+    let mut image = vec![0x4e, 0x45, 0x53, 0x1a, 2, 0, 0x12, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+    image.extend(prg);
+    Rom::new(&mut &image[..]).unwrap()
+}
+
+fn boot(save: Option<&[u8]>) -> Vec<u8> {
+    let mut nes = Nes::new(mmc1_rom()).unwrap();
+    if let Some(save) = save {
+        nes.load_battery_ram(save);
+    }
+    nes.power_on();
+    nes.run_frame();
+    nes.battery_ram().expect("MMC1 board with a battery exposes its PRG RAM")
+}
+
+#[test]
+fn mmc1_save_round_trip() {
+    let first = boot(None);
+    assert_eq!(first.len(), 0x2000);
+    assert_eq!(first[0], 1, "write after disabling PRG RAM should be ignored");
+
+    let second = boot(Some(&first));
+    assert_eq!(second[0], 2);
+    assert_eq!(&second[1..], &first[1..]);
+}
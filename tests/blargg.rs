@@ -0,0 +1,143 @@
+// blargg's test ROMs, run headless through the $6000 status protocol. Each
+// entry is a path under the test ROM directory (see tests/common) and what
+// the emulator is expected to do with it today. Missing ROMs are skipped.
+//
+// Everything is expected to pass apart from the known failures, and a Pass
+// entry that fails the run fails the test. A ROM found failing for a reason
+// that isn't going to be fixed straight away moves to Fail, with the reason.
+//
+// kevtris's nestest predates the protocol and is covered by the CPU trace
+// comparison instead.
+
+extern crate rnes;
+
+mod common;
+
+use rnes::nes::test_rom;
+use rnes::Nes;
+use rnes::Rom;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Pass,
+    // A known failure and why. These are run but not asserted on, so that a
+    // fix shows up as a note to move the entry over.
+    Fail(&'static str),
+}
+
+use Expect::*;
+
+const NO_MMC3: Expect = Fail("mapper 4 isn't supported");
+
+const COMPATIBILITY: &[(&str, Expect)] = &[
+    ("instr_test-v5/rom_singles/01-basics.nes", Pass),
+    ("instr_test-v5/rom_singles/02-implied.nes", Pass),
+    ("instr_test-v5/rom_singles/03-immediate.nes", Pass),
+    ("instr_test-v5/rom_singles/04-zero_page.nes", Pass),
+    ("instr_test-v5/rom_singles/05-zp_xy.nes", Pass),
+    ("instr_test-v5/rom_singles/06-absolute.nes", Pass),
+    ("instr_test-v5/rom_singles/07-abs_xy.nes", Pass),
+    ("instr_test-v5/rom_singles/08-ind_x.nes", Pass),
+    ("instr_test-v5/rom_singles/09-ind_y.nes", Pass),
+    ("instr_test-v5/rom_singles/10-branches.nes", Pass),
+    ("instr_test-v5/rom_singles/11-stack.nes", Pass),
+    ("instr_test-v5/rom_singles/12-jmp_jsr.nes", Pass),
+    ("instr_test-v5/rom_singles/13-rts.nes", Pass),
+    ("instr_test-v5/rom_singles/14-rti.nes", Pass),
+    ("instr_test-v5/rom_singles/15-brk.nes", Pass),
+    ("instr_test-v5/rom_singles/16-special.nes", Pass),
+
+    ("instr_misc/rom_singles/01-abs_x_wrap.nes", Pass),
+    ("instr_misc/rom_singles/02-branch_wrap.nes", Pass),
+    ("instr_misc/rom_singles/03-dummy_reads.nes", Pass),
+    ("instr_misc/rom_singles/04-dummy_reads_apu.nes", Pass),
+
+    ("instr_timing/rom_singles/1-instr_timing.nes", Pass),
+    ("instr_timing/rom_singles/2-branch_timing.nes", Pass),
+
+    ("cpu_interrupts_v2/rom_singles/1-cli_latency.nes", Pass),
+    ("cpu_interrupts_v2/rom_singles/2-nmi_and_brk.nes", Pass),
+    ("cpu_interrupts_v2/rom_singles/3-nmi_and_irq.nes", Pass),
+    ("cpu_interrupts_v2/rom_singles/4-irq_and_dma.nes", Pass),
+    ("cpu_interrupts_v2/rom_singles/5-branch_delays_irq.nes", Pass),
+
+    ("ppu_vbl_nmi/rom_singles/01-vbl_basics.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/02-vbl_set_time.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/03-vbl_clear_time.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/04-nmi_control.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/05-nmi_timing.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/06-suppression.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/07-nmi_on_timing.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/08-nmi_off_timing.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/09-even_odd_frames.nes", Pass),
+    ("ppu_vbl_nmi/rom_singles/10-even_odd_timing.nes", Pass),
+
+    ("cpu_reset/registers.nes", Pass),
+    ("cpu_reset/ram_after_reset.nes", Pass),
+
+    ("cpu_dummy_writes/cpu_dummy_writes_oam.nes", Pass),
+    ("cpu_dummy_writes/cpu_dummy_writes_ppumem.nes", Pass),
+
+    ("apu_test/rom_singles/1-len_ctr.nes", Pass),
+    ("apu_test/rom_singles/2-len_table.nes", Pass),
+    ("apu_test/rom_singles/3-irq_flag.nes", Pass),
+    ("apu_test/rom_singles/4-jitter.nes", Pass),
+    ("apu_test/rom_singles/5-len_timing.nes", Pass),
+    ("apu_test/rom_singles/6-irq_flag_timing.nes", Pass),
+    ("apu_test/rom_singles/7-dmc_basics.nes", Pass),
+    ("apu_test/rom_singles/8-dmc_rates.nes", Pass),
+
+    ("mmc3_test_2/rom_singles/1-clocking.nes", NO_MMC3),
+    ("mmc3_test_2/rom_singles/2-details.nes", NO_MMC3),
+    ("mmc3_test_2/rom_singles/3-A12_clocking.nes", NO_MMC3),
+    ("mmc3_test_2/rom_singles/4-scanline_timing.nes", NO_MMC3),
+    ("mmc3_test_2/rom_singles/5-MMC3.nes", NO_MMC3),
+];
+
+fn run(name: &str) -> Option<Result<test_rom::TestResult, String>> {
+    let data = common::load_rom(name)?;
+
+    let rom = match Rom::new(&mut &data[..]) {
+        Ok(rom) => rom,
+        Err(e) => return Some(Err(e.to_string())),
+    };
+
+    let mut nes = match Nes::new(rom) {
+        Ok(nes) => nes,
+        Err(e) => return Some(Err(e.to_string())),
+    };
+
+    nes.power_on();
+    Some(Ok(test_rom::run_test_rom(&mut nes, test_rom::DEFAULT_FRAME_LIMIT)))
+}
+
+#[test]
+fn compatibility() {
+    let mut regressions = Vec::new();
+
+    for &(name, expect) in COMPATIBILITY {
+        let result = match run(name) {
+            Some(result) => result,
+            None => continue,
+        };
+
+        let passed = result.as_ref().map(|result| result.passed()).unwrap_or(false);
+        let summary = match result {
+            Ok(result) => format!("{:?} after {} frames: {}", result.outcome, result.frames, result.text.trim_end()),
+            Err(e) => e,
+        };
+
+        match expect {
+            Pass if !passed => {
+                eprintln!("FAIL {}: {}", name, summary);
+                regressions.push(name);
+            },
+            Fail(reason) if passed => {
+                eprintln!("note: {} now passes (listed as failing: {})", name, reason);
+            },
+            _ => eprintln!("ok   {}", name),
+        }
+    }
+
+    assert!(regressions.is_empty(), "expected to pass: {:?}", regressions);
+}
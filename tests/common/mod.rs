@@ -0,0 +1,29 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+// Test ROMs aren't redistributable, so they live outside the repository. Put
+// them in tests/roms or point RNES_TEST_ROMS at a directory holding them.
+pub fn rom_dir() -> PathBuf {
+    match env::var_os("RNES_TEST_ROMS") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms"),
+    }
+}
+
+// Reads a file from the ROM directory, or returns None (with a note on
+// stderr) so the calling test can pass vacuously when it isn't there.
+pub fn load_rom(name: &str) -> Option<Vec<u8>> {
+    let path = rom_dir().join(name);
+    let mut data = Vec::new();
+    match File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+        Ok(_) => Some(data),
+        Err(_) => {
+            eprintln!("skipping: {} not found", path.display());
+            None
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// When the core polls for interrupts: not during an interrupt sequence, and
+// not on the last cycle of a taken branch that stays on its page.
+
+extern crate rnes;
+
+use rnes::nes::ricoh2a03::cpu_bus::CpuBus;
+use rnes::nes::ricoh2a03::{Registers, Ricoh2A03};
+
+const CODE: u16 = 0x0400;
+const NMI_HANDLER: u16 = 0x0600;
+const IRQ_HANDLER: u16 = 0x0700;
+
+// 64 KiB of RAM, with NMI or IRQ asserted from a given cycle on. The CPU
+// samples a line as it was on the previous cycle, so a line asserted from
+// cycle n is first seen on cycle n + 1.
+struct TimedBus {
+    memory: Box<[u8]>,
+    cycles: u64,
+    irq_at: Option<u64>,
+    nmi_at: Option<u64>,
+}
+
+impl CpuBus for TimedBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_at.is_some_and(|at| self.cycles >= at)
+    }
+
+    fn nmi(&self) -> bool {
+        self.nmi_at.is_some_and(|at| self.cycles >= at)
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+}
+
+// Runs `code` from $0400 with the given flags. Both handlers, and the code
+// after it, are NOPs.
+fn boot(code: &[u8], p: u8, irq_at: Option<u64>, nmi_at: Option<u64>) -> Ricoh2A03<TimedBus> {
+    let mut memory = vec![0xea; 0x10000].into_boxed_slice();
+    memory[CODE as usize..CODE as usize + code.len()].copy_from_slice(code);
+    memory[0xfffa] = NMI_HANDLER as u8;
+    memory[0xfffb] = (NMI_HANDLER >> 8) as u8;
+    memory[0xfffe] = IRQ_HANDLER as u8;
+    memory[0xffff] = (IRQ_HANDLER >> 8) as u8;
+
+    let mut cpu = Ricoh2A03::new(TimedBus { memory, cycles: 0, irq_at, nmi_at });
+    cpu.set_registers(Registers { pc: CODE, a: 0, x: 0, y: 0, s: 0xfd, p });
+    cpu
+}
+
+fn step(cpu: &mut Ricoh2A03<TimedBus>) -> u16 {
+    cpu.step();
+    cpu.registers().pc
+}
+
+#[test]
+fn nmi_during_brk_vector_fetch() {
+    // Seen on cycle 6, after BRK has pushed the status and can no longer be
+    // hijacked. The IRQ handler's first instruction still runs.
+    let mut cpu = boot(&[0x00, 0x00], 0x04, None, Some(5));
+    assert_eq!(step(&mut cpu), IRQ_HANDLER);
+    assert_eq!(step(&mut cpu), IRQ_HANDLER + 1);
+    assert_eq!(step(&mut cpu), NMI_HANDLER);
+}
+
+#[test]
+fn nmi_hijacks_brk() {
+    let mut cpu = boot(&[0x00, 0x00], 0x04, None, Some(4));
+    assert_eq!(step(&mut cpu), NMI_HANDLER);
+    assert_eq!(step(&mut cpu), NMI_HANDLER + 1);
+}
+
+#[test]
+fn nmi_during_taken_branch() {
+    // BNE +2, taken without crossing a page.
+    let branch = [0xd0, 0x02];
+
+    // Seen on the second cycle: taken straight after the branch.
+    let mut cpu = boot(&branch, 0x04, None, Some(1));
+    assert_eq!(step(&mut cpu), CODE + 4);
+    assert_eq!(step(&mut cpu), NMI_HANDLER);
+
+    // Seen on the last cycle: one more instruction runs first.
+    let mut cpu = boot(&branch, 0x04, None, Some(2));
+    assert_eq!(step(&mut cpu), CODE + 4);
+    assert_eq!(step(&mut cpu), CODE + 5);
+    assert_eq!(step(&mut cpu), NMI_HANDLER);
+}
+
+#[test]
+fn irq_during_taken_branch() {
+    let branch = [0xd0, 0x02];
+
+    let mut cpu = boot(&branch, 0x00, Some(1), None);
+    assert_eq!(step(&mut cpu), CODE + 4);
+    assert_eq!(step(&mut cpu), IRQ_HANDLER);
+
+    let mut cpu = boot(&branch, 0x00, Some(2), None);
+    assert_eq!(step(&mut cpu), CODE + 4);
+    assert_eq!(step(&mut cpu), CODE + 5);
+    assert_eq!(step(&mut cpu), IRQ_HANDLER);
+}
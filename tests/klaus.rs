@@ -0,0 +1,139 @@
+// Klaus Dormann's 6502 functional and decimal tests, run on the bare CPU core.
+// https://github.com/Klaus2m5/6502_65C02_functional_tests
+
+extern crate rnes;
+
+mod common;
+
+use rnes::nes::ricoh2a03::cpu_bus::FlatBus;
+use rnes::nes::ricoh2a03::Ricoh2A03;
+
+const STEP_LIMIT: u64 = 100_000_000;
+
+fn cpu_with(image: &[u8], address: u16) -> Ricoh2A03<FlatBus> {
+    let mut bus = FlatBus::new();
+    bus.load(address, image);
+    let mut cpu = Ricoh2A03::new(bus);
+    cpu.set_decimal_mode(true);
+    cpu
+}
+
+// Steps until an instruction leaves PC where it found it, which is how both
+// tests signal that they've finished or failed. Returns the trapping PC.
+fn run_until_trap(cpu: &mut Ricoh2A03<FlatBus>) -> u16 {
+    for _ in 0..STEP_LIMIT {
+        let pc = cpu.pc();
+        if cpu.bus().memory()[pc as usize] == 0xdb {
+            return pc;
+        }
+        cpu.step();
+        if cpu.pc() == pc || cpu.halted() {
+            return pc;
+        }
+    }
+    panic!("no trap after {} instructions, PC at {:04X}", STEP_LIMIT, cpu.pc());
+}
+
+#[test]
+fn functional_test() {
+    let image = match common::load_rom("6502_functional_test.bin") {
+        Some(image) => image,
+        None => return,
+    };
+
+    let mut cpu = cpu_with(&image, 0x0000);
+    cpu.set_pc(0x0400);
+    let trap = run_until_trap(&mut cpu);
+    assert_eq!(trap, 0x3469, "trapped at {:04X}, see the listing for which test failed", trap);
+}
+
+#[test]
+fn decimal_test() {
+    let image = match common::load_rom("6502_decimal_test.bin") {
+        Some(image) => image,
+        None => return,
+    };
+
+    // Accept either the bare program assembled at $0200 or a full 64K image.
+    let origin = if image.len() == 0x10000 { 0x0000 } else { 0x0200 };
+    let mut cpu = cpu_with(&image, origin);
+    cpu.set_pc(0x0200);
+    run_until_trap(&mut cpu);
+
+    let error = cpu.bus().memory()[0x000b];
+    assert_eq!(error, 0, "decimal test reported an error");
+}
+
+// Doesn't need any files: checks ADC and SBC in decimal mode against Bruce
+// Clark's description of the NMOS 6502 for every operand and carry.
+#[test]
+fn decimal_arithmetic() {
+    let mut cpu = Ricoh2A03::new(FlatBus::new());
+    cpu.set_decimal_mode(true);
+
+    for &carry in &[false, true] {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                for &subtract in &[false, true] {
+                    // SED; CLC/SEC; LDA #a; ADC/SBC #b; STA $10; PHP; PLA; STA $11
+                    let program = [
+                        0xf8,
+                        if carry { 0x38 } else { 0x18 },
+                        0xa9, a,
+                        if subtract { 0xe9 } else { 0x69 }, b,
+                        0x85, 0x10,
+                        0x08,
+                        0x68,
+                        0x85, 0x11,
+                    ];
+                    cpu.bus_mut().load(0x0200, &program);
+                    cpu.set_pc(0x0200);
+                    for _ in 0..8 {
+                        cpu.step();
+                    }
+
+                    let memory = cpu.bus().memory();
+                    let p = memory[0x11];
+                    let result = (memory[0x10], p & 0x01 != 0, p & 0x02 != 0, p & 0x40 != 0, p & 0x80 != 0);
+                    let expected = if subtract { sbc(a, b, carry) } else { adc(a, b, carry) };
+                    assert_eq!(result, expected, "{} {:02X} {:02X} carry {}",
+                        if subtract { "SBC" } else { "ADC" }, a, b, carry);
+                }
+            }
+        }
+    }
+}
+
+// (A, C, Z, V, N)
+fn adc(a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let (a, b, c) = (a as i32, b as i32, carry as i32);
+    let mut low = (a & 0x0f) + (b & 0x0f) + c;
+    if low >= 0x0a {
+        low = ((low + 0x06) & 0x0f) + 0x10;
+    }
+    let mut result = (a & 0xf0) + (b & 0xf0) + low;
+    let signed = (a & 0xf0) as u8 as i8 as i32 + (b & 0xf0) as u8 as i8 as i32 + low;
+    let n = result & 0x80 != 0;
+    let v = !(-128..=127).contains(&signed);
+    if result >= 0xa0 {
+        result += 0x60;
+    }
+    let z = (a + b + c) & 0xff == 0;
+    (result as u8, result >= 0x100, z, v, n)
+}
+
+fn sbc(a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let (a, b, c) = (a as i32, b as i32, carry as i32);
+    let mut low = (a & 0x0f) - (b & 0x0f) + c - 1;
+    if low < 0 {
+        low = ((low - 0x06) & 0x0f) - 0x10;
+    }
+    let mut result = (a & 0xf0) - (b & 0xf0) + low;
+    if result < 0 {
+        result -= 0x60;
+    }
+    // The flags come from the binary subtraction.
+    let binary = a - b + c - 1;
+    let v = (a ^ b) & (a ^ binary) & 0x80 != 0;
+    (result as u8, binary >= 0, binary & 0xff == 0, v, binary & 0x80 != 0)
+}
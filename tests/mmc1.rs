@@ -0,0 +1,147 @@
+// MMC1 bank switching, driven directly through the mapper interface.
+
+extern crate rnes;
+
+use rnes::nes::mapper::Mapper;
+use rnes::nes::mappers::mmc1::Mmc1;
+use rnes::nes::rom::{MirrorMode, ROM_CHR_BANK_SIZE, ROM_PRG_BANK_SIZE};
+use rnes::nes::state::{StateReader, StateWriter};
+use rnes::{Rom, StateError};
+
+// Eight 16 KiB PRG banks and eight 4 KiB CHR banks, each filled with its own
+// number.
+fn mmc1() -> Mmc1 {
+    let mut image = vec![0x4e, 0x45, 0x53, 0x1a, 8, 4, 0x10, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+    for bank in 0..8 {
+        image.extend(vec![bank as u8; ROM_PRG_BANK_SIZE]);
+    }
+    for bank in 0..8 {
+        image.extend(vec![bank as u8; ROM_CHR_BANK_SIZE / 2]);
+    }
+    Mmc1::new(Rom::new(&mut &image[..]).unwrap())
+}
+
+// A serial write of the low five bits of `value`, with the cycles of a STA
+// between each write.
+fn write_register(mapper: &mut Mmc1, address: u16, value: u8) {
+    for bit in 0..5 {
+        for _ in 0..4 {
+            mapper.clock();
+        }
+        mapper.write_prg(address, (value >> bit) & 1);
+    }
+}
+
+fn prg_banks(mapper: &Mmc1) -> (u8, u8) {
+    (mapper.read_prg(0x8000), mapper.read_prg(0xc000))
+}
+
+fn chr_banks(mapper: &Mmc1) -> (u8, u8) {
+    (mapper.read_chr(0x0000), mapper.read_chr(0x1000))
+}
+
+#[test]
+fn prg_banking() {
+    let mut mapper = mmc1();
+
+    // Power on in mode 3: switchable $8000, last bank fixed at $C000.
+    assert_eq!(prg_banks(&mapper), (0, 7));
+
+    write_register(&mut mapper, 0xe000, 3);
+    assert_eq!(prg_banks(&mapper), (3, 7));
+
+    // Mode 2: first bank fixed at $8000, switchable $C000.
+    write_register(&mut mapper, 0x8000, 0x08);
+    assert_eq!(prg_banks(&mapper), (0, 3));
+
+    // Mode 0: 32 KiB switching, ignoring the low bit of the bank number.
+    write_register(&mut mapper, 0x8000, 0x00);
+    assert_eq!(prg_banks(&mapper), (2, 3));
+
+    // Bank numbers wrap to the size of the ROM.
+    write_register(&mut mapper, 0x8000, 0x0c);
+    write_register(&mut mapper, 0xe000, 0x0d);
+    assert_eq!(prg_banks(&mapper), (5, 7));
+}
+
+#[test]
+fn chr_banking() {
+    let mut mapper = mmc1();
+
+    // 4 KiB mode: each half switched on its own.
+    write_register(&mut mapper, 0x8000, 0x1c);
+    write_register(&mut mapper, 0xa000, 5);
+    write_register(&mut mapper, 0xc000, 2);
+    assert_eq!(chr_banks(&mapper), (5, 2));
+
+    // 8 KiB mode: $A000 picks an even/odd pair and $C000 is ignored.
+    write_register(&mut mapper, 0x8000, 0x0c);
+    assert_eq!(chr_banks(&mapper), (4, 5));
+}
+
+#[test]
+fn mirroring() {
+    let mut mapper = mmc1();
+
+    write_register(&mut mapper, 0x8000, 0x0c);
+    assert!(matches!(mapper.mirroring(), MirrorMode::OneScreen));
+    write_register(&mut mapper, 0x8000, 0x0d);
+    assert!(matches!(mapper.mirroring(), MirrorMode::OneScreenUpper));
+    write_register(&mut mapper, 0x8000, 0x0e);
+    assert!(matches!(mapper.mirroring(), MirrorMode::Vertical));
+    write_register(&mut mapper, 0x8000, 0x0f);
+    assert!(matches!(mapper.mirroring(), MirrorMode::Horizontal));
+}
+
+#[test]
+fn consecutive_writes() {
+    let mut mapper = mmc1();
+
+    // A read-modify-write writes twice on back to back cycles; only the first
+    // reaches the shift register.
+    mapper.clock();
+    mapper.clock();
+    mapper.write_prg(0xe000, 1);
+    mapper.clock();
+    mapper.write_prg(0xe000, 1);
+    for _ in 0..4 {
+        mapper.clock();
+        mapper.clock();
+        mapper.write_prg(0xe000, 0);
+    }
+    assert_eq!(prg_banks(&mapper), (1, 7));
+
+    // Likewise after a reset, so INC on a $FF byte resets and nothing more.
+    mapper.clock();
+    mapper.clock();
+    mapper.write_prg(0xe000, 1);
+    mapper.clock();
+    mapper.clock();
+    mapper.write_prg(0xe000, 0x80);
+    mapper.clock();
+    mapper.write_prg(0xe000, 0x01);
+    write_register(&mut mapper, 0xe000, 6);
+    assert_eq!(prg_banks(&mapper), (6, 7));
+}
+
+#[test]
+fn corrupt_state() {
+    let mut mapper = mmc1();
+    write_register(&mut mapper, 0xe000, 3);
+
+    let mut state = StateWriter::new();
+    mapper.save_state(&mut state);
+    let mut state = state.into_inner();
+
+    let mut loaded = mmc1();
+    assert!(loaded.load_state(&mut StateReader::new(&state)).is_ok());
+    assert_eq!(prg_banks(&loaded), (3, 7));
+
+    // The PRG bank register, one byte before the end.
+    let prg_bank = state.len() - 2;
+    state[prg_bank] = 0x20;
+    match mmc1().load_state(&mut StateReader::new(&state)) {
+        Err(StateError::Corrupt) => {}
+        _ => panic!("loaded an out of range PRG bank"),
+    }
+}
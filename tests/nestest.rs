@@ -0,0 +1,29 @@
+// kevtris's nestest, run from $C000 and checked line by line against the
+// Nintendulator log that ships with it (nestest.nes and nestest.log).
+
+extern crate rnes;
+
+mod common;
+
+use rnes::nes::nestest;
+use rnes::Nes;
+use rnes::Rom;
+
+#[test]
+fn nestest() {
+    let (rom, log) = match (common::load_rom("nestest.nes"), common::load_rom("nestest.log")) {
+        (Some(rom), Some(log)) => (rom, log),
+        _ => return,
+    };
+
+    let mut nes = Nes::new(Rom::new(&mut &rom[..]).unwrap()).unwrap();
+    let result = nestest::run_nestest(&mut nes, Some(&String::from_utf8_lossy(&log)));
+
+    if let Some(divergence) = result.divergence {
+        panic!("diverges at line {}\n{}\nexpected {:?}\n  actual {:?}",
+            divergence.line, divergence.context.join("\n"), divergence.expected, divergence.actual);
+    }
+
+    assert!(result.finished, "stopped after {} instructions", result.instructions);
+    assert_eq!((result.official, result.unofficial), (0, 0));
+}
@@ -0,0 +1,45 @@
+// OAM DMA length, started on either CPU cycle parity.
+
+extern crate rnes;
+
+use rnes::nes::rom::ROM_PRG_BANK_SIZE;
+use rnes::{Nes, Rom};
+
+fn dma_rom() -> Rom {
+    let code = [
+        0x8d, 0x14, 0x40,   // C000: STA $4014
+        0x8d, 0x14, 0x40,   //       STA $4014
+        0xa5, 0x00,         //       LDA $00
+        0x8d, 0x14, 0x40,   //       STA $4014
+        0x4c, 0x0b, 0xc0,   // C00B: JMP $C00B
+    ];
+
+    let mut prg = vec![0u8; ROM_PRG_BANK_SIZE];
+    prg[..code.len()].copy_from_slice(&code);
+    for offset in (prg.len() - 6..prg.len()).step_by(2) {
+        prg[offset] = 0x00;
+        prg[offset + 1] = 0xc0;
+    }
+
+    let mut image = vec![0x4e, 0x45, 0x53, 0x1a, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    image.extend(prg);
+    Rom::new(&mut &image[..]).unwrap()
+}
+
+#[test]
+fn alignment_follows_cpu_parity() {
+    let mut nes = Nes::new(dma_rom()).unwrap();
+    nes.power_on();
+
+    // The first DMA leaves the CPU on a known parity. The second STA starts
+    // its DMA a whole number of cycle pairs later, and the three cycles of
+    // LDA shift the third onto the other parity.
+    nes.run_cycles(1);
+    let second = nes.run_cycles(1) - 4;
+    assert_eq!(nes.run_cycles(1), 3);
+    let third = nes.run_cycles(1) - 4;
+
+    let mut lengths = [second, third];
+    lengths.sort();
+    assert_eq!(lengths, [513, 514]);
+}
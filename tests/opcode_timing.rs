@@ -0,0 +1,60 @@
+// Checks the cycle counts in the opcode table against what the core actually
+// spends, one official opcode at a time on a bare 64 KiB bus.
+
+extern crate rnes;
+
+use rnes::nes::ricoh2a03::cpu_bus::FlatBus;
+use rnes::nes::ricoh2a03::opcodes::{Mode, OPCODES};
+use rnes::nes::ricoh2a03::{Registers, Ricoh2A03};
+
+const CODE: u16 = 0x0400;
+
+// Runs one instruction at `pc` and returns the cycles it took. Operands point
+// at $02F0 (directly, or through a zero page pointer at $10) so that indexing
+// by $FF crosses a page and indexing by 0 doesn't.
+fn run(opcode: u8, pc: u16, operand: u8, index: u8, p: u8) -> u64 {
+    let mut bus = FlatBus::new();
+    bus.load(pc, &[opcode, operand, 0x02]);
+    bus.load(0x0010, &[0xf0, 0x02]);
+
+    let mut cpu = Ricoh2A03::new(bus);
+    cpu.set_registers(Registers { pc, a: 0, x: index, y: index, s: 0xfd, p });
+    cpu.step();
+    cpu.bus().cycles()
+}
+
+#[test]
+fn official_opcode_cycles() {
+    for (opcode, op) in OPCODES.iter().enumerate() {
+        if !op.official {
+            continue;
+        }
+
+        let opcode = opcode as u8;
+
+        if op.mode == Mode::Relative {
+            // Exactly one of "all flags clear" and "all flags set" takes any
+            // given branch; a taken branch costs one more cycle, and one more
+            // again if it lands on another page.
+            let near = [run(opcode, CODE, 0x10, 0, 0x00), run(opcode, CODE, 0x10, 0, 0xff)];
+            let far = [run(opcode, CODE + 0xf0, 0x20, 0, 0x00), run(opcode, CODE + 0xf0, 0x20, 0, 0xff)];
+
+            assert!(op.page_penalty, "{:02X} {}", opcode, op.mnemonic());
+            assert_eq!(near.iter().min(), Some(&(op.cycles as u64)), "{:02X} {} not taken", opcode, op.mnemonic());
+            assert_eq!(near.iter().max(), Some(&(op.cycles as u64 + 1)), "{:02X} {} taken", opcode, op.mnemonic());
+            assert_eq!(far.iter().max(), Some(&(op.cycles as u64 + 2)), "{:02X} {} taken across a page", opcode, op.mnemonic());
+            continue;
+        }
+
+        let penalty = if op.page_penalty { 1 } else { 0 };
+        let operand = match op.mode {
+            Mode::IndirectX | Mode::IndirectY => 0x10,
+            _ => 0xf0,
+        };
+
+        assert_eq!(run(opcode, CODE, operand, 0x00, 0x04), op.cycles as u64,
+            "{:02X} {} {:?}", opcode, op.mnemonic(), op.mode);
+        assert_eq!(run(opcode, CODE, operand, 0xff, 0x04), op.cycles as u64 + penalty,
+            "{:02X} {} {:?} across a page", opcode, op.mnemonic(), op.mode);
+    }
+}
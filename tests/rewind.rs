@@ -0,0 +1,65 @@
+// Rewinding through frames that fall between snapshots.
+
+extern crate rnes;
+
+use rnes::nes::rom::ROM_PRG_BANK_SIZE;
+use rnes::{Nes, Rewind, Rom};
+
+// Polls the first controller in a loop and keeps a running sum of what it
+// reads, so the machine state depends on the input of every frame.
+fn input_rom() -> Rom {
+    let code = [
+        0xa9, 0x01,         // C000: LDA #$01
+        0x8d, 0x16, 0x40,   //       STA $4016
+        0xa9, 0x00,         //       LDA #$00
+        0x8d, 0x16, 0x40,   //       STA $4016
+        0xa2, 0x08,         //       LDX #$08
+        0xad, 0x16, 0x40,   // C00C: LDA $4016
+        0x4a,               //       LSR A
+        0x26, 0x00,         //       ROL $00
+        0xca,               //       DEX
+        0xd0, 0xf7,         //       BNE $C00C
+        0xa5, 0x00,         //       LDA $00
+        0x18,               //       CLC
+        0x65, 0x01,         //       ADC $01
+        0x85, 0x01,         //       STA $01
+        0x4c, 0x00, 0xc0,   //       JMP $C000
+    ];
+
+    let mut prg = vec![0u8; ROM_PRG_BANK_SIZE];
+    prg[..code.len()].copy_from_slice(&code);
+    for offset in (prg.len() - 6..prg.len()).step_by(2) {
+        prg[offset] = 0x00;
+        prg[offset + 1] = 0xc0;
+    }
+
+    let mut image = vec![0x4e, 0x45, 0x53, 0x1a, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    image.extend(prg);
+    Rom::new(&mut &image[..]).unwrap()
+}
+
+#[test]
+fn replays_recorded_input() {
+    let mut nes = Nes::new(input_rom()).unwrap();
+    let mut rewind = Rewind::new(5, 1 << 20);
+    let mut states = Vec::new();
+
+    nes.power_on();
+    for frame in 0..23u8 {
+        nes.set_input(0, frame.wrapping_mul(37));
+        nes.run_frame();
+        rewind.record(&nes);
+        states.push((nes.frame(), nes.save_state()));
+    }
+
+    states.pop();
+    while let Some((frame, state)) = states.pop() {
+        if !rewind.rewind_frame(&mut nes) {
+            break;
+        }
+        assert_eq!(nes.frame(), frame);
+        assert!(nes.save_state() == state, "state differs after rewinding to frame {}", frame);
+    }
+
+    assert!(states.len() < 5, "history ran out with {} frames left", states.len());
+}
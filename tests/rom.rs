@@ -0,0 +1,165 @@
+// iNES / NES 2.0 header parsing, on images built in memory.
+
+extern crate rnes;
+
+use rnes::nes::rom::{RomFormat, Timing, ROM_CHR_BANK_SIZE, ROM_PRG_BANK_SIZE, ROM_TRAINER_SIZE};
+use rnes::{Rom, RomError};
+
+fn header(prg: u8, chr: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+    let mut header = vec![0u8; 16];
+    header[0..4].copy_from_slice(b"NES\x1a");
+    header[4] = prg;
+    header[5] = chr;
+    header[6] = flags6;
+    header[7] = flags7;
+    header
+}
+
+fn image(mut header: Vec<u8>, prg_size: usize, chr_size: usize) -> Vec<u8> {
+    if header[6] & 0x04 != 0 {
+        header.extend((0..ROM_TRAINER_SIZE).map(|i| i as u8));
+    }
+    header.extend(vec![0xaa; prg_size]);
+    header.extend(vec![0x55; chr_size]);
+    header
+}
+
+fn parse(data: &[u8]) -> Result<Rom, RomError> {
+    Rom::new(&mut &data[..])
+}
+
+#[test]
+fn ines() {
+    let mut header = header(2, 1, 0x13, 0x40);
+    header[9] = 0x01;
+    let rom = parse(&image(header, 2 * ROM_PRG_BANK_SIZE, ROM_CHR_BANK_SIZE)).unwrap();
+
+    assert_eq!(rom.format(), RomFormat::INes);
+    assert_eq!(rom.mapper(), 0x41);
+    assert_eq!(rom.prg_banks(), 2);
+    assert_eq!(rom.chr_banks(), 1);
+    assert!(rom.has_battery());
+    assert_eq!(rom.prg_nvram_size(), 0x2000);
+    assert_eq!(rom.timing(), Timing::Pal);
+    assert!(rom.trainer().is_none());
+}
+
+#[test]
+fn archaic_header_ignores_upper_bytes() {
+    let mut header = header(1, 0, 0x10, 0x40);
+    header[7..16].copy_from_slice(b"DiskDude!");
+    let rom = parse(&image(header, ROM_PRG_BANK_SIZE, 0)).unwrap();
+
+    assert_eq!(rom.format(), RomFormat::Archaic);
+    assert_eq!(rom.mapper(), 1);
+    assert_eq!(rom.chr_ram_size(), ROM_CHR_BANK_SIZE);
+}
+
+#[test]
+fn nes20_sizes() {
+    let mut header = header(0x02, 0x01, 0x00, 0x08);
+    header[8] = 0x21;
+    header[9] = 0x11;
+    header[10] = 0x70;
+    header[11] = 0x07;
+    header[12] = 0x03;
+    let prg_size = 0x102 * ROM_PRG_BANK_SIZE;
+    let chr_size = 0x101 * ROM_CHR_BANK_SIZE;
+    let rom = parse(&image(header, prg_size, chr_size)).unwrap();
+
+    assert_eq!(rom.format(), RomFormat::Nes20);
+    assert_eq!(rom.mapper(), 0x100);
+    assert_eq!(rom.submapper(), 2);
+    assert_eq!(rom.prg_rom_size(), prg_size);
+    assert_eq!(rom.chr_rom_size(), chr_size);
+    assert_eq!(rom.prg_ram_size(), 0);
+    assert_eq!(rom.prg_nvram_size(), 8192);
+    assert_eq!(rom.chr_ram_size(), 8192);
+    assert_eq!(rom.timing(), Timing::Dendy);
+}
+
+#[test]
+fn nes20_exponent_size() {
+    // 2^15 * 3 = 96 KiB of PRG, 2^13 * 1 = 8 KiB of CHR.
+    let mut header = header((15 << 2) | 1, 13 << 2, 0x00, 0x08);
+    header[9] = 0xff;
+    let rom = parse(&image(header, 96 * 1024, 8 * 1024)).unwrap();
+
+    assert_eq!(rom.prg_banks(), 6);
+    assert_eq!(rom.chr_banks(), 1);
+}
+
+#[test]
+fn trainer() {
+    let rom = parse(&image(header(1, 1, 0x04, 0x00), ROM_PRG_BANK_SIZE, ROM_CHR_BANK_SIZE)).unwrap();
+
+    let trainer = rom.trainer().unwrap();
+    assert_eq!(trainer.len(), ROM_TRAINER_SIZE);
+    assert_eq!(trainer[0x1ff], 0xff);
+    assert_eq!(rom.read_prg(0), 0xaa);
+    assert_eq!(rom.read_chr(0), 0x55);
+}
+
+#[test]
+fn bad_magic() {
+    let mut data = image(header(1, 0, 0, 0), ROM_PRG_BANK_SIZE, 0);
+    data[3] = 0;
+    match parse(&data) {
+        Err(RomError::BadMagic) => (),
+        other => panic!("expected BadMagic, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn empty_prg() {
+    match parse(&image(header(0, 1, 0, 0), 0, ROM_CHR_BANK_SIZE)) {
+        Err(RomError::BadPrgSize(0)) => (),
+        other => panic!("expected BadPrgSize, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn prg_smaller_than_a_bank() {
+    // NES 2.0 exponent form: 2^13 * 1 = 8 KiB.
+    let mut header = header(13 << 2, 0, 0x00, 0x08);
+    header[9] = 0x0f;
+    match parse(&image(header, 8192, 0)) {
+        Err(RomError::BadPrgSize(8192)) => (),
+        other => panic!("expected BadPrgSize, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn truncation() {
+    let full = image(header(2, 1, 0x04, 0x00), 2 * ROM_PRG_BANK_SIZE, ROM_CHR_BANK_SIZE);
+    let prg_start = 16 + ROM_TRAINER_SIZE;
+    let chr_start = prg_start + 2 * ROM_PRG_BANK_SIZE;
+
+    match parse(&full[..10]) {
+        Err(RomError::TruncatedHeader) => (),
+        other => panic!("expected TruncatedHeader, got {:?}", other.err()),
+    }
+
+    match parse(&full[..prg_start - 1]) {
+        Err(RomError::TruncatedHeader) => (),
+        other => panic!("expected TruncatedHeader, got {:?}", other.err()),
+    }
+
+    match parse(&full[..prg_start + 100]) {
+        Err(RomError::TruncatedPrg { expected, found }) => {
+            assert_eq!(expected, 2 * ROM_PRG_BANK_SIZE);
+            assert_eq!(found, 100);
+        },
+        other => panic!("expected TruncatedPrg, got {:?}", other.err()),
+    }
+
+    match parse(&full[..chr_start + 1]) {
+        Err(RomError::TruncatedChr { expected, found }) => {
+            assert_eq!(expected, ROM_CHR_BANK_SIZE);
+            assert_eq!(found, 1);
+        },
+        other => panic!("expected TruncatedChr, got {:?}", other.err()),
+    }
+
+    assert!(parse(&full).is_ok());
+}
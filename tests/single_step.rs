@@ -0,0 +1,247 @@
+// Per-opcode single-step vectors in the format of the SingleStepTests
+// (formerly ProcessorTests) suite: each file holds a JSON array of cases with
+// the registers and RAM before and after one instruction, plus every bus cycle
+// the instruction performs. Use the nes6502 set, which has BCD disabled.
+// https://github.com/SingleStepTests/65x02
+
+extern crate rnes;
+extern crate serde_json;
+
+mod common;
+
+use rnes::nes::ricoh2a03::cpu_bus::CpuBus;
+use rnes::nes::ricoh2a03::cpu_bus::FlatBus;
+use rnes::nes::ricoh2a03::Registers;
+use rnes::nes::ricoh2a03::Ricoh2A03;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Read;
+
+// After a JAM the real chip keeps the bus busy in ways the vectors capture
+// but the core doesn't model cycle by cycle.
+const JAM_OPCODES: [u8; 12] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xb2, 0xd2, 0xf2,
+];
+
+// Failures shown per opcode before the rest are only counted.
+const REPORT_LIMIT: usize = 5;
+
+// A few cases in the same format, worked out by hand from the 6502's
+// documented bus behaviour, so that the harness itself is exercised without
+// the full suite: an immediate load, an indexed store across a page (dummy
+// read), a read-modify-write (dummy write), JSR, a taken branch across a page,
+// an unofficial opcode and BRK.
+const INLINE_CASES: &str = r#"[
+    {"name": "a9 42", "initial": {"pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 38, "ram": [[1024, 169], [1025, 66]]},
+     "final": {"pc": 1026, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[1024, 169], [1025, 66]]},
+     "cycles": [[1024, 169, "read"], [1025, 66, "read"]]},
+    {"name": "9d f0 02", "initial": {"pc": 1024, "s": 253, "a": 85, "x": 32, "y": 0, "p": 36, "ram": [[1024, 157], [1025, 240], [1026, 2], [528, 17]]},
+     "final": {"pc": 1027, "s": 253, "a": 85, "x": 32, "y": 0, "p": 36, "ram": [[528, 17], [784, 85]]},
+     "cycles": [[1024, 157, "read"], [1025, 240, "read"], [1026, 2, "read"], [528, 17, "read"], [784, 85, "write"]]},
+    {"name": "e6 10", "initial": {"pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[1024, 230], [1025, 16], [16, 127]]},
+     "final": {"pc": 1026, "s": 253, "a": 0, "x": 0, "y": 0, "p": 164, "ram": [[16, 128]]},
+     "cycles": [[1024, 230, "read"], [1025, 16, "read"], [16, 127, "read"], [16, 127, "write"], [16, 128, "write"]]},
+    {"name": "20 34 12", "initial": {"pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[1024, 32], [1025, 52], [1026, 18], [509, 0]]},
+     "final": {"pc": 4660, "s": 251, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[509, 4], [508, 2]]},
+     "cycles": [[1024, 32, "read"], [1025, 52, "read"], [509, 0, "read"], [509, 4, "write"], [508, 2, "write"], [1026, 18, "read"]]},
+    {"name": "d0 20", "initial": {"pc": 1264, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[1264, 208], [1265, 32], [1266, 234], [1042, 234]]},
+     "final": {"pc": 1298, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": []},
+     "cycles": [[1264, 208, "read"], [1265, 32, "read"], [1266, 234, "read"], [1042, 234, "read"]]},
+    {"name": "a7 10", "initial": {"pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[1024, 167], [1025, 16], [16, 128]]},
+     "final": {"pc": 1026, "s": 253, "a": 128, "x": 128, "y": 0, "p": 164, "ram": []},
+     "cycles": [[1024, 167, "read"], [1025, 16, "read"], [16, 128, "read"]]},
+    {"name": "00 00", "initial": {"pc": 1024, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[1024, 0], [1025, 0], [65534, 0], [65535, 128]]},
+     "final": {"pc": 32768, "s": 250, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[509, 4], [508, 2], [507, 48]]},
+     "cycles": [[1024, 0, "read"], [1025, 0, "read"], [509, 4, "write"], [508, 2, "write"], [507, 48, "write"], [65534, 0, "read"], [65535, 128, "read"]]}
+]"#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Access {
+    Read,
+    Write,
+}
+
+type Cycle = (u16, u8, Access);
+
+// A flat 64 KiB bus that logs every access in order.
+struct RecordingBus {
+    memory: FlatBus,
+    cycles: Vec<Cycle>,
+}
+
+impl CpuBus for RecordingBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.memory.read(address);
+        self.cycles.push((address, value, Access::Read));
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory.write(address, value);
+        self.cycles.push((address, value, Access::Write));
+    }
+
+    fn tick(&mut self) {
+        self.memory.tick();
+    }
+
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.memory.peek(address)
+    }
+}
+
+struct State {
+    registers: Registers,
+    ram: Vec<(u16, u8)>,
+}
+
+struct Case {
+    name: String,
+    initial: State,
+    expected: State,
+    cycles: Vec<Cycle>,
+}
+
+fn number(value: &Value) -> u64 {
+    value.as_u64().expect("expected a number")
+}
+
+fn parse_state(value: &Value) -> State {
+    let registers = Registers {
+        pc: number(&value["pc"]) as u16,
+        a: number(&value["a"]) as u8,
+        x: number(&value["x"]) as u8,
+        y: number(&value["y"]) as u8,
+        s: number(&value["s"]) as u8,
+        p: number(&value["p"]) as u8,
+    };
+    let ram = value["ram"].as_array().expect("expected a ram array").iter()
+        .map(|entry| (number(&entry[0]) as u16, number(&entry[1]) as u8))
+        .collect();
+    State { registers, ram }
+}
+
+fn parse_cycle(value: &Value) -> Cycle {
+    let access = match value[2].as_str() {
+        Some("read") => Access::Read,
+        Some("write") => Access::Write,
+        other => panic!("unknown bus access {:?}", other),
+    };
+    (number(&value[0]) as u16, number(&value[1]) as u8, access)
+}
+
+fn parse_cases(json: &str) -> Vec<Case> {
+    let cases: Value = serde_json::from_str(json).expect("malformed test file");
+    cases.as_array().expect("expected an array of cases").iter()
+        .map(|case| Case {
+            name: case["name"].as_str().unwrap_or("").to_string(),
+            initial: parse_state(&case["initial"]),
+            expected: parse_state(&case["final"]),
+            cycles: case["cycles"].as_array().expect("expected a cycles array").iter()
+                .map(parse_cycle)
+                .collect(),
+        })
+        .collect()
+}
+
+// Bits 4 and 5 of P don't exist in the register, so they're ignored.
+fn same_registers(a: &Registers, b: &Registers) -> bool {
+    Registers { p: a.p | 0x30, ..*a } == Registers { p: b.p | 0x30, ..*b }
+}
+
+// Runs one case and describes how it went wrong, if it did.
+fn run_case(cpu: &mut Ricoh2A03<RecordingBus>, case: &Case) -> Option<String> {
+    for &(address, value) in &case.initial.ram {
+        cpu.bus_mut().memory.write(address, value);
+    }
+    cpu.set_registers(case.initial.registers);
+    cpu.bus_mut().cycles.clear();
+
+    cpu.step();
+
+    let mut problems = Vec::new();
+    let registers = cpu.registers();
+    if !same_registers(&registers, &case.expected.registers) {
+        problems.push(format!("registers {:02X?}, expected {:02X?}", registers, case.expected.registers));
+    }
+    for &(address, value) in &case.expected.ram {
+        let actual = cpu.bus().memory.peek(address);
+        if actual != value {
+            problems.push(format!("${:04X} = {:02X}, expected {:02X}", address, actual, value));
+        }
+    }
+    let cycles = &cpu.bus().cycles;
+    if *cycles != case.cycles {
+        problems.push(format!("cycles {:04X?}\n    expected {:04X?}", cycles, case.cycles));
+    }
+
+    // Leave memory as it was found so cases don't leak into each other.
+    for &(address, _) in case.initial.ram.iter().chain(&case.expected.ram) {
+        cpu.bus_mut().memory.write(address, 0);
+    }
+
+    if problems.is_empty() {
+        None
+    } else {
+        Some(format!("{}: {}", case.name, problems.join("; ")))
+    }
+}
+
+fn recording_cpu() -> Ricoh2A03<RecordingBus> {
+    Ricoh2A03::new(RecordingBus { memory: FlatBus::new(), cycles: Vec::new() })
+}
+
+#[test]
+fn inline_cases() {
+    let mut cpu = recording_cpu();
+    let cases = parse_cases(INLINE_CASES);
+    assert_eq!(cases.len(), 7);
+
+    let failures: Vec<String> = cases.iter().filter_map(|case| run_case(&mut cpu, case)).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn single_step() {
+    let dir = common::rom_dir().join("nes6502");
+    if !dir.is_dir() {
+        eprintln!("skipping: {} not found", dir.display());
+        return;
+    }
+
+    let mut cpu = recording_cpu();
+    let mut failed_opcodes = Vec::new();
+
+    for opcode in 0..=255u8 {
+        if JAM_OPCODES.contains(&opcode) {
+            continue;
+        }
+
+        let path = dir.join(format!("{:02x}.json", opcode));
+        let mut json = String::new();
+        if File::open(&path).and_then(|mut file| file.read_to_string(&mut json)).is_err() {
+            eprintln!("skipping: {} not found", path.display());
+            continue;
+        }
+
+        let cases = parse_cases(&json);
+        let failures: Vec<String> = cases.iter().filter_map(|case| run_case(&mut cpu, case)).collect();
+        if !failures.is_empty() {
+            eprintln!("opcode {:02X}: {} of {} cases failed", opcode, failures.len(), cases.len());
+            for failure in failures.iter().take(REPORT_LIMIT) {
+                eprintln!("  {}", failure);
+            }
+            failed_opcodes.push(opcode);
+        }
+    }
+
+    assert!(failed_opcodes.is_empty(), "opcodes failed: {:02X?}", failed_opcodes);
+}